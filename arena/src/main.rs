@@ -0,0 +1,240 @@
+use bimap::BiMap;
+use crossbeam_channel::{Receiver, Sender};
+use quoridor_core::{rulebooks::*, *};
+use std::{
+    error::Error,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tbmp::*;
+
+mod players;
+mod stats;
+
+use common::{
+    board::{SizedGame, BOARD_SIZE, BOARD_SIZES},
+    notation::format_move,
+    record::{GameRecord, Termination},
+};
+use players::{parse_player, Player};
+use stats::Tally;
+
+generate_rulebook! {
+    StandardQuoridor,
+    FreeQuoridor,
+}
+
+type GameFn = Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>;
+
+impl SizedGame for QGameType {
+    type Game = (Vec<QAgent>, GameFn);
+
+    fn standard_game(&self) -> Self::Game {
+        self.new_game()
+    }
+}
+
+const USAGE: &str = "usage: arena [OPTIONS] PLAYER_A PLAYER_B
+
+Players are one of:
+    ai[:DEPTH]     the built-in engine, searching DEPTH plies (default 2)
+    random         picks random legal moves
+    engine:PATH    an external engine speaking the uqi protocol
+
+Options:
+    --games N          games per game type (default 10)
+    --types LIST       comma separated game types (default standard,free)
+    --out DIR          where game records are written (default arena_games)
+    --size N           board size (default 9, the only one for now)
+    --max-plies N      plies before a game is called a draw (default 300)
+    --movetime MS      thinking time for external engines (default 1000)
+    --seed N           seed for the built-in players";
+
+struct Options {
+    players: Vec<String>,
+    games: u32,
+    types: Vec<QGameType>,
+    out: PathBuf,
+    size: u8,
+    max_plies: usize,
+    movetime: u64,
+    seed: u64,
+}
+
+fn gtstr(gt: &QGameType) -> &'static str {
+    match gt {
+        QGameType::StandardQuoridor => "standard",
+        QGameType::FreeQuoridor => "free",
+    }
+}
+
+fn parse_game_type(s: &str) -> Result<QGameType, Box<dyn Error>> {
+    match s {
+        "standard" => Ok(QGameType::StandardQuoridor),
+        "free" => Ok(QGameType::FreeQuoridor),
+        _ => Err(format!("unknown game type '{}'", s).into()),
+    }
+}
+
+fn parse_options() -> Result<Options, Box<dyn Error>> {
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+    let mut options = Options {
+        players: vec![],
+        games: 10,
+        types: vec![QGameType::StandardQuoridor, QGameType::FreeQuoridor],
+        out: PathBuf::from("arena_games"),
+        size: BOARD_SIZE,
+        max_plies: 300,
+        movetime: 1000,
+        seed,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+        match &arg[..] {
+            "--games" => options.games = value()?.parse()?,
+            "--types" => {
+                options.types = value()?
+                    .split(',')
+                    .map(parse_game_type)
+                    .collect::<Result<_, _>>()?
+            }
+            "--out" => options.out = value()?.into(),
+            "--size" => options.size = value()?.parse()?,
+            "--max-plies" => options.max_plies = value()?.parse()?,
+            "--movetime" => options.movetime = value()?.parse()?,
+            "--seed" => options.seed = value()?.parse()?,
+            "--help" | "-h" => return Err(USAGE.into()),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg).into()),
+            _ => options.players.push(arg),
+        }
+    }
+
+    if options.players.len() != 2 {
+        return Err(USAGE.into());
+    }
+    if !BOARD_SIZES.contains(&options.size) {
+        return Err(format!("unsupported board size {}", options.size).into());
+    }
+    Ok(options)
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let options = parse_options()?;
+    fs::create_dir_all(&options.out)?;
+
+    let mut a = parse_player(&options.players[0], options.movetime, options.seed)?;
+    let mut b = parse_player(&options.players[1], options.movetime, options.seed ^ 0x9e37_79b9)?;
+    println!("{} vs {}", a.name(), b.name());
+
+    for game_type in options.types.iter() {
+        let mut tally = Tally::default();
+        for i in 0..options.games {
+            // Alternate sides so neither player keeps the first move.
+            let a_side = (i % 2) as PlayerID;
+            let mut sides: Vec<&mut dyn Player> = vec![a.as_mut(), b.as_mut()];
+            if a_side == 1 {
+                sides.reverse();
+            }
+
+            let record = play_game(*game_type, options.size, &mut sides, options.max_plies)?;
+            match record.winner {
+                Some(w) if w == a_side => tally.wins += 1,
+                Some(_) => tally.losses += 1,
+                None => tally.draws += 1,
+            }
+            tally.plies += record.moves.len();
+
+            let path = options
+                .out
+                .join(format!("{}-{:04}.json", gtstr(game_type), i + 1));
+            fs::write(path, serde_json::to_string_pretty(&record)?)?;
+        }
+        println!("\n{}: {} as player A", gtstr(game_type), a.name());
+        println!("{}", tally);
+    }
+    Ok(())
+}
+
+/// Plays one game between `players`, indexed by side.
+fn play_game(
+    game_type: QGameType,
+    size: u8,
+    players: &mut [&mut dyn Player],
+    max_plies: usize,
+) -> Result<GameRecord, Box<dyn Error>> {
+    let (agents, mut step) = game_type
+        .new_game_with_size(size)
+        .ok_or("unsupported board size")?;
+    let mut seats: Vec<(QAgent, Option<(Quoridor, PlayerID)>)> =
+        agents.into_iter().map(|agent| (agent, None)).collect();
+    for player in players.iter_mut() {
+        player.new_game(size)?;
+    }
+
+    let names: Vec<_> = players.iter().map(|p| p.name()).collect();
+    let record = |moves, winner, termination| GameRecord {
+        game_type: gtstr(&game_type).into(),
+        size,
+        players: names.clone(),
+        moves,
+        winner,
+        termination,
+    };
+
+    let mut moves = vec![];
+    // Counts requests rather than accepted moves, so rejected moves can't loop forever.
+    for _ in 0..max_plies {
+        for (agent, state) in seats.iter_mut() {
+            while let Ok(event) = agent.recv_event() {
+                match event {
+                    QGameEvent::GameStart(game, side) => *state = Some((game, side)),
+                    QGameEvent::MoveHappened(qmv) => {
+                        if let Some((game, side)) = state {
+                            if *side == 0 {
+                                moves.push(format_move(&qmv));
+                            }
+                            game.apply_move(&qmv);
+                        }
+                    }
+                    QGameEvent::GameEnd(winner) => {
+                        return Ok(record(moves, winner, Termination::Goal));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let (agent, game, side) = seats
+            .iter()
+            .find_map(|(agent, state)| match state {
+                Some((game, side)) if game.turn_of() == *side => Some((agent, game, *side)),
+                _ => None,
+            })
+            .ok_or("the game didn't start")?;
+        let opponent = Some((side + 1) % players.len() as PlayerID);
+
+        let qmv = match players[side as usize].choose(game, &moves) {
+            Ok(qmv) => qmv,
+            Err(e) => {
+                eprintln!("{} forfeits: {}", players[side as usize].name(), e);
+                return Ok(record(moves, opponent, Termination::Forfeit));
+            }
+        };
+        agent.send_move(RulebookMove::wrap(game, &qmv)).unwrap();
+        if step().is_err() {
+            eprintln!("{} played an illegal move", players[side as usize].name());
+            return Ok(record(moves, opponent, Termination::Forfeit));
+        }
+    }
+    Ok(record(moves, None, Termination::MoveLimit))
+}
@@ -0,0 +1,203 @@
+use common::{
+    ai::{random_move, Ai, Rng},
+    board::Board,
+    engine::{EngineCommand, EngineReply},
+    notation::parse_move,
+};
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use quoridor_core::*;
+use std::{
+    error::Error,
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+const GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// Something that can pick moves in the arena.
+pub trait Player {
+    fn name(&self) -> String;
+
+    fn new_game(&mut self, _size: u8) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    /// Picks a move for the side to move, given the moves played so far.
+    fn choose(&mut self, game: &Quoridor, moves: &[String]) -> Result<Move, Box<dyn Error>>;
+}
+
+/// Parses `ai[:DEPTH]`, `random` or `engine:PATH`.
+pub fn parse_player(spec: &str, movetime: u64, seed: u64) -> Result<Box<dyn Player>, Box<dyn Error>> {
+    let (kind, arg) = match spec.find(':') {
+        Some(i) => (&spec[..i], Some(&spec[i + 1..])),
+        None => (spec, None),
+    };
+    Ok(match (kind, arg) {
+        ("ai", depth) => {
+            let depth = depth.map(str::parse).transpose()?.unwrap_or(2);
+            Box::new(Builtin(Ai::new(depth, seed)))
+        }
+        ("random", None) => Box::new(Random(Rng::new(seed))),
+        ("engine", Some(path)) => Box::new(Engine::spawn(path, movetime)?),
+        _ => return Err(format!("unknown player '{}'", spec).into()),
+    })
+}
+
+pub struct Builtin(Ai);
+
+impl Player for Builtin {
+    fn name(&self) -> String {
+        format!("ai:{}", self.0.depth)
+    }
+
+    fn choose(&mut self, game: &Quoridor, _: &[String]) -> Result<Move, Box<dyn Error>> {
+        Ok(self.0.best_move(&Board::from_game(game)).ok_or("no legal moves")?)
+    }
+}
+
+pub struct Random(Rng);
+
+impl Player for Random {
+    fn name(&self) -> String {
+        "random".into()
+    }
+
+    fn choose(&mut self, game: &Quoridor, _: &[String]) -> Result<Move, Box<dyn Error>> {
+        Ok(random_move(&Board::from_game(game), &mut self.0).ok_or("no legal moves")?)
+    }
+}
+
+/// An external engine speaking the protocol from `common::engine`.
+pub struct Engine {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    movetime: u64,
+}
+
+impl Engine {
+    pub fn spawn(path: &str, movetime: u64) -> Result<Self, Box<dyn Error>> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+
+        let (tx, lines) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                let sent = line.map(|line| tx.send(line).is_ok()).unwrap_or(false);
+                if !sent {
+                    break;
+                }
+            }
+        });
+
+        let mut engine = Engine {
+            name: path.into(),
+            child,
+            stdin,
+            lines,
+            movetime,
+        };
+
+        engine.send(EngineCommand::Uqi)?;
+        let mut name = None;
+        engine
+            .wait_for(HANDSHAKE_TIMEOUT, |reply| match reply {
+                EngineReply::Id { key, value } if key == "name" => {
+                    name = Some(value);
+                    None
+                }
+                EngineReply::UqiOk => Some(()),
+                _ => None,
+            })?
+            .ok_or("engine didn't answer uqi")?;
+        if let Some(name) = name {
+            engine.name = name;
+        }
+        engine.send(EngineCommand::IsReady)?;
+        engine
+            .wait_for(HANDSHAKE_TIMEOUT, |reply| match reply {
+                EngineReply::ReadyOk => Some(()),
+                _ => None,
+            })?
+            .ok_or("engine didn't answer isready")?;
+
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: EngineCommand) -> Result<(), Box<dyn Error>> {
+        writeln!(self.stdin, "{}", command)?;
+        Ok(())
+    }
+
+    /// Reads lines until one matches `want`, giving up after `limit`.
+    fn wait_for<T>(
+        &self,
+        limit: Duration,
+        mut want: impl FnMut(EngineReply) -> Option<T>,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        let deadline = Instant::now() + limit;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(remaining) {
+                Ok(line) => {
+                    if let Some(found) = EngineReply::parse(&line).and_then(&mut want) {
+                        return Ok(Some(found));
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => return Err("engine closed its output".into()),
+            }
+        }
+    }
+}
+
+impl Player for Engine {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self, size: u8) -> Result<(), Box<dyn Error>> {
+        self.send(EngineCommand::NewGame { size })
+    }
+
+    fn choose(&mut self, game: &Quoridor, moves: &[String]) -> Result<Move, Box<dyn Error>> {
+        fn best(reply: EngineReply) -> Option<String> {
+            match reply {
+                EngineReply::BestMove(qmv) => Some(qmv),
+                _ => None,
+            }
+        }
+
+        self.send(EngineCommand::Position {
+            moves: moves.to_vec(),
+        })?;
+        self.send(EngineCommand::Go {
+            movetime: self.movetime,
+        })?;
+
+        let limit = Duration::from_millis(self.movetime) + GRACE_PERIOD;
+        let notation = match self.wait_for(limit, best)? {
+            Some(notation) => notation,
+            None => {
+                self.send(EngineCommand::Stop)?;
+                self.wait_for(GRACE_PERIOD, best)?.ok_or("ran out of time")?
+            }
+        };
+        Ok(parse_move(&notation, game)?)
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send(EngineCommand::Quit);
+        let _ = self.child.kill();
+    }
+}
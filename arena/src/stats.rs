@@ -0,0 +1,139 @@
+use std::fmt;
+
+/// z value for a 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// Results from the point of view of the first player on the command line.
+#[derive(Default)]
+pub struct Tally {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub plies: usize,
+}
+
+impl Tally {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    pub fn average_length(&self) -> f64 {
+        self.plies as f64 / self.games() as f64
+    }
+
+    /// Estimated Elo difference with its 95% confidence interval.
+    pub fn elo(&self) -> (f64, f64, f64) {
+        let n = self.games() as f64;
+        let score = self.score();
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / n;
+        let margin = Z_95 * (variance / n).sqrt();
+        (
+            elo_from_score(score),
+            elo_from_score(score - margin),
+            elo_from_score(score + margin),
+        )
+    }
+}
+
+fn elo_from_score(score: f64) -> f64 {
+    if score <= 0.0 {
+        f64::NEG_INFINITY
+    } else if score >= 1.0 {
+        f64::INFINITY
+    } else {
+        -400.0 * (1.0 / score - 1.0).log10()
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.games() == 0 {
+            return write!(f, "no games played");
+        }
+        let (elo, low, high) = self.elo();
+        writeln!(
+            f,
+            "+{} ={} -{} in {} games, score {:.1}%, average length {:.1} plies",
+            self.wins,
+            self.draws,
+            self.losses,
+            self.games(),
+            self.score() * 100.0,
+            self.average_length(),
+        )?;
+        write!(
+            f,
+            "Elo difference {:+.1}, 95% confidence interval [{:+.1}, {:+.1}]",
+            elo, low, high
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tally(wins: u32, draws: u32, losses: u32) -> Tally {
+        Tally {
+            wins,
+            draws,
+            losses,
+            plies: 30 * (wins + draws + losses) as usize,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn scores() {
+        let t = tally(20, 10, 10);
+        assert_eq!(t.games(), 40);
+        assert!(close(t.score(), 0.625));
+        assert!(close(t.average_length(), 30.0));
+    }
+
+    #[test]
+    fn elo_and_interval() {
+        let (elo, low, high) = tally(20, 10, 10).elo();
+        assert!(close(elo, 88.739_499_846), "{}", elo);
+        assert!(close(low, -2.417_554_121), "{}", low);
+        assert!(close(high, 194.086_971_252), "{}", high);
+
+        // An even match is centred on zero.
+        let (elo, low, high) = tally(5, 0, 5).elo();
+        assert!(close(elo, 0.0));
+        assert!(close(low, -high));
+    }
+
+    #[test]
+    fn elo_of_one_sided_results() {
+        let (sweep, _, _) = tally(4, 0, 0).elo();
+        assert!(sweep.is_infinite() && sweep > 0.0);
+        let (whitewash, _, _) = tally(0, 0, 4).elo();
+        assert!(whitewash.is_infinite() && whitewash < 0.0);
+        // The interval runs off the scale before the estimate does.
+        let (elo, low, high) = tally(3, 0, 1).elo();
+        assert!(close(elo, 190.848_501_888), "{}", elo);
+        assert!(close(low, -126.455_648_129), "{}", low);
+        assert!(high.is_infinite() && high > 0.0);
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Tally::default().to_string(), "no games played");
+        assert_eq!(
+            tally(20, 10, 10).to_string(),
+            "+20 =10 -10 in 40 games, score 62.5%, average length 30.0 plies\n\
+             Elo difference +88.7, 95% confidence interval [-2.4, +194.1]"
+        );
+    }
+}
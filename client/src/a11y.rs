@@ -0,0 +1,69 @@
+use common::{
+    notation::{format_square, format_wall},
+    pawn_owner,
+};
+use quoridor_core::*;
+use tbmp_core::*;
+
+/// Says `text` through the page's live region.
+pub fn announce(text: &str) {
+    if let Some(region) = element("announcer") {
+        // Clearing first makes screen readers repeat an identical message.
+        region.set_text_content(None);
+        region.set_text_content(Some(text));
+    }
+}
+
+/// Keeps the board's text description, which the canvas points to, up to date.
+pub fn update_description(game: &Quoridor, side: PlayerID) {
+    if let Some(description) = element("description") {
+        description.set_text_content(Some(&describe(game, side)));
+    }
+}
+
+fn element(id: &str) -> Option<web_sys::Element> {
+    web_sys::window()?.document()?.get_element_by_id(id)
+}
+
+/// Describes the position in words, e.g. "You are player 0, your move.
+/// Player 0: pawn on e1, 10 walls left. ...".
+pub fn describe(game: &Quoridor, side: PlayerID) -> String {
+    let mut text = format!("You are player {}, ", side);
+    if game.turn_of() == side {
+        text.push_str("your move.");
+    } else {
+        text.push_str(&format!("player {} to move.", game.turn_of()));
+    }
+
+    let mut pawns: Vec<_> = game.pawns().iter().map(|(&id, &pos)| (id, pos)).collect();
+    pawns.sort_by_key(|(id, _)| *id);
+    for (player, walls) in game.wall_counts().enumerate() {
+        let squares: Vec<_> = pawns
+            .iter()
+            .filter(|(id, _)| pawn_owner(game, *id) as usize == player)
+            .map(|(_, pos)| format_square(*pos))
+            .collect();
+        text.push_str(&format!(
+            " Player {}: pawn on {}, {} walls left.",
+            player,
+            squares.join(" and "),
+            walls
+        ));
+    }
+
+    let walls: Vec<_> = game.walls().iter().map(format_wall).collect();
+    if walls.is_empty() {
+        text.push_str(" No walls placed.");
+    } else {
+        text.push_str(&format!(" Walls: {}.", walls.join(", ")));
+    }
+    text
+}
+
+/// What the keyboard cursor is on, for announcing as it moves.
+pub fn describe_square(game: &Quoridor, pos: Position) -> String {
+    match game.pawns().get_by_right(&pos) {
+        Some(&id) => format!("{}, player {} pawn", format_square(pos), pawn_owner(game, id)),
+        None => format_square(pos),
+    }
+}
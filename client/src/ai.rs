@@ -0,0 +1,101 @@
+//! The AI opponent for offline games. The search runs in a Web Worker, which
+//! loads this same module and calls `best_move`, so the page stays responsive
+//! while it thinks.
+
+use common::{ai::Ai, board::Board, notation::format_move};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// The script the worker runs, see `static/ai-worker.js`.
+const WORKER_SCRIPT: &str = "/static/ai-worker.js";
+
+#[derive(Serialize, Deserialize)]
+struct Request {
+    board: Board,
+    depth: u8,
+    seed: u64,
+}
+
+/// Called in the worker: the move to play for the request's board, in notation,
+/// or nothing if there's no move.
+#[wasm_bindgen]
+pub fn best_move(request: &str) -> Option<String> {
+    let request: Request = serde_json::from_str(request).ok()?;
+    Ai::new(request.depth, request.seed)
+        .best_move(&request.board)
+        .map(|qmv| format_move(&qmv))
+}
+
+pub struct Worker {
+    worker: web_sys::Worker,
+    /// How the last search ended, not yet taken: the move found, or `None` if
+    /// there was none or the worker failed.
+    reply: Rc<RefCell<Option<Option<String>>>>,
+    thinking: Rc<Cell<bool>>,
+    _on_message: Closure<dyn FnMut(web_sys::MessageEvent)>,
+    _on_error: Closure<dyn FnMut(web_sys::ErrorEvent)>,
+}
+
+impl Worker {
+    pub fn new() -> Option<Worker> {
+        let mut options = web_sys::WorkerOptions::new();
+        options.type_(web_sys::WorkerType::Module);
+        let worker = web_sys::Worker::new_with_options(WORKER_SCRIPT, &options).ok()?;
+
+        let reply = Rc::new(RefCell::new(None));
+        let thinking = Rc::new(Cell::new(false));
+        // Every message ends a search, with or without a move.
+        let (rc, busy) = (Rc::clone(&reply), Rc::clone(&thinking));
+        let on_message = Closure::wrap(Box::new(move |e: web_sys::MessageEvent| {
+            *rc.borrow_mut() = Some(e.data().as_string());
+            busy.set(false);
+        }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        let (rc, busy) = (Rc::clone(&reply), Rc::clone(&thinking));
+        let on_error = Closure::wrap(Box::new(move |e: web_sys::ErrorEvent| {
+            web_sys::console::error_1(&JsValue::from_str(&e.message()));
+            *rc.borrow_mut() = Some(None);
+            busy.set(false);
+        }) as Box<dyn FnMut(web_sys::ErrorEvent)>);
+        worker.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+
+        Some(Worker {
+            worker,
+            reply,
+            thinking,
+            _on_message: on_message,
+            _on_error: on_error,
+        })
+    }
+
+    /// Asks for a move on `board`, unless a search is already running, and
+    /// says whether it did.
+    pub fn think(&mut self, board: &Board, depth: u8) -> bool {
+        if self.thinking.get() {
+            return false;
+        }
+        let request = Request {
+            board: board.clone(),
+            depth,
+            seed: js_sys::Date::now() as u64,
+        };
+        let request = serde_json::to_string(&request).unwrap();
+        let posted = self
+            .worker
+            .post_message(&JsValue::from_str(&request))
+            .is_ok();
+        self.thinking.set(posted);
+        posted
+    }
+
+    /// How the last search ended, once it has: the move found, or `None` if
+    /// there is no move to play.
+    pub fn reply(&mut self) -> Option<Option<String>> {
+        self.reply.borrow_mut().take()
+    }
+}
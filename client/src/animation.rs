@@ -0,0 +1,36 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// How long a move takes to play out, in milliseconds.
+const DURATION: f64 = 250.0;
+
+/// Milliseconds since the page loaded.
+pub fn now() -> f64 {
+    web_sys::window()
+        .and_then(|w| w.performance())
+        .map_or(0.0, |p| p.now())
+}
+
+/// Whether the user asked their system for less motion.
+pub fn reduced_motion() -> bool {
+    web_sys::window()
+        .and_then(|w| w.match_media("(prefers-reduced-motion: reduce)").ok()?)
+        .map_or(false, |query| query.matches())
+}
+
+/// How far an animation started at `start` has got, eased, from 0 to 1.
+pub fn progress(start: Option<f64>) -> f64 {
+    let t = match start {
+        Some(start) => f64::min((now() - start) / DURATION, 1.0),
+        None => 1.0,
+    };
+    // Ease out, so moves arrive gently.
+    1.0 - (1.0 - t) * (1.0 - t)
+}
+
+pub fn request_frame(f: &Closure<dyn FnMut()>) {
+    web_sys::window()
+        .unwrap()
+        .request_animation_frame(f.as_ref().unchecked_ref())
+        .unwrap();
+}
@@ -0,0 +1,240 @@
+//! Correspondence games, see `common::correspondence`. The server keeps the
+//! game, so this page sends the player's moves there and checks back now and
+//! then for their opponent's.
+
+use crate::{a11y, local, status};
+use common::{
+    correspondence::{GameView, MoveRequest},
+    notation::format_move,
+    record::Termination,
+};
+use quoridor_core::*;
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    rc::Rc,
+};
+use tbmp_core::*;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// How often the server is asked for the opponent's move.
+const POLL_INTERVAL_MS: f64 = 20_000.0;
+/// The local storage key of the player's tokens by game, which the lobby page
+/// lists the games of.
+const STORAGE_KEY: &str = "correspondence";
+
+/// The game and token a page query like `?mode=correspondence&id=…&token=…`
+/// asks for.
+pub fn from_query(search: &str) -> Option<(String, String)> {
+    if local::query_param(search, "mode")? != "correspondence" {
+        return None;
+    }
+    let id = local::decode(local::query_param(search, "id")?)?;
+    let token = local::decode(local::query_param(search, "token")?)?;
+    Some((id, token))
+}
+
+/// Keeps the token of a game, so the lobby page can list it.
+pub fn remember(id: &str, token: &str) -> Option<()> {
+    let storage = web_sys::window()?.local_storage().ok()??;
+    let mut tokens: BTreeMap<String, String> = storage
+        .get_item(STORAGE_KEY)
+        .ok()?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    tokens.insert(id.into(), token.into());
+    storage
+        .set_item(STORAGE_KEY, &serde_json::to_string(&tokens).ok()?)
+        .ok()
+}
+
+/// Sends a request about a game, handing `then` the game as the server answers
+/// it, or `None` if it refused or couldn't be reached.
+fn request(
+    method: &str,
+    url: &str,
+    body: Option<String>,
+    then: impl FnOnce(Option<GameView>) + 'static,
+) -> Option<()> {
+    let request = web_sys::XmlHttpRequest::new().ok()?;
+    request.open(method, url).ok()?;
+    let response = request.clone();
+    // `loadend` comes after failures too, unlike `load`.
+    let on_load_end = Closure::once(move || {
+        let view = Some(&response)
+            .filter(|r| r.status().ok() == Some(200))
+            .and_then(|r| r.response_text().ok()?)
+            .and_then(|text| serde_json::from_str(&text).ok());
+        then(view)
+    });
+    request.set_onloadend(Some(on_load_end.as_ref().unchecked_ref()));
+    on_load_end.forget();
+    match body {
+        Some(body) => {
+            request
+                .set_request_header("Content-Type", "application/json")
+                .ok()?;
+            request.send_with_opt_str(Some(&body)).ok()
+        }
+        None => request.send().ok(),
+    }
+}
+
+fn game_url(id: &str, token: &str) -> String {
+    format!(
+        "/correspondence/{}?token={}",
+        String::from(js_sys::encode_uri_component(id)),
+        String::from(js_sys::encode_uri_component(token))
+    )
+}
+
+/// Fetches a game from the server as the token's side, handing it to `then`.
+pub fn fetch(id: &str, token: &str, then: impl FnOnce(GameView) + 'static) -> Option<()> {
+    request("GET", &game_url(id, token), None, move |view| match view {
+        Some(view) => then(view),
+        None => status::show_result("Game not found", "There's no such game.", false),
+    })
+}
+
+/// Says until when the player to move has to move.
+fn show_deadline(view: &GameView, side: PlayerID) {
+    let text = match view.deadline {
+        Some(deadline) => {
+            let date = js_sys::Date::new(&JsValue::from_f64(deadline as f64 * 1000.0));
+            let date = String::from(date.to_locale_string("default", &JsValue::UNDEFINED));
+            if view.to_move() == side {
+                format!("Your move, until {}", date)
+            } else {
+                format!("Their move, until {}", date)
+            }
+        }
+        None if view.open => String::from("Waiting for an opponent to join"),
+        None => String::new(),
+    };
+    status::set_deadline(&text);
+}
+
+/// A correspondence game being played on this page, kept in step with the server.
+pub struct Link {
+    id: String,
+    token: String,
+    side: PlayerID,
+    players: usize,
+    /// Moves played so far on this page.
+    ply: usize,
+    /// The game as the server last answered, not yet looked at.
+    latest: Rc<RefCell<Option<GameView>>>,
+    /// Whether the server refused a move of the player's.
+    refused: Rc<Cell<bool>>,
+    /// Whether a request for the game is on its way.
+    polling: Rc<Cell<bool>>,
+    last_poll: f64,
+    ended: bool,
+}
+
+impl Link {
+    /// Links a game just fetched, `None` unless the token holds a seat in it.
+    pub fn new(view: &GameView, token: String) -> Option<Link> {
+        let link = Link {
+            id: view.id.clone(),
+            token,
+            side: view.seat?,
+            players: view.players.len(),
+            ply: view.moves.len(),
+            latest: Rc::new(RefCell::new(Some(view.clone()))),
+            refused: Rc::new(Cell::new(false)),
+            polling: Rc::new(Cell::new(false)),
+            last_poll: js_sys::Date::now(),
+            ended: false,
+        };
+        remember(&link.id, &link.token);
+        Some(link)
+    }
+
+    pub fn side(&self) -> PlayerID {
+        self.side
+    }
+
+    /// Follows a move about to be played, sending it to the server if it's the
+    /// player's.
+    pub fn observe(&mut self, qmv: &Move) {
+        let ply = self.ply;
+        self.ply += 1;
+        if ply % self.players != self.side as usize {
+            return;
+        }
+        let body = MoveRequest {
+            token: self.token.clone(),
+            ply,
+            notation: format_move(qmv),
+        };
+        let url = format!(
+            "/correspondence/{}/move",
+            String::from(js_sys::encode_uri_component(&self.id))
+        );
+        let (latest, refused) = (Rc::clone(&self.latest), Rc::clone(&self.refused));
+        request(
+            "POST",
+            &url,
+            serde_json::to_string(&body).ok(),
+            move |view| match view {
+                Some(view) => *latest.borrow_mut() = Some(view),
+                None => refused.set(true),
+            },
+        );
+    }
+
+    /// Asks the server how the game stands, every so often.
+    fn poll(&mut self) {
+        let now = js_sys::Date::now();
+        if self.ended || self.polling.get() || now - self.last_poll < POLL_INTERVAL_MS {
+            return;
+        }
+        self.last_poll = now;
+        self.polling.set(true);
+        let (latest, polling) = (Rc::clone(&self.latest), Rc::clone(&self.polling));
+        request("GET", &game_url(&self.id, &self.token), None, move |view| {
+            if view.is_some() {
+                *latest.borrow_mut() = view;
+            }
+            polling.set(false);
+        });
+    }
+
+    /// The opponent's next move once the server has it. Shows how the game
+    /// stands meanwhile, and the end of it if someone ran out of time.
+    pub fn next_move(&mut self) -> Option<String> {
+        if self.refused.replace(false) && !self.ended {
+            self.ended = true;
+            status::show_result(
+                "Move not sent",
+                "The server didn't take your move. Reload the page to see the game as it stands.",
+                false,
+            );
+        }
+        self.poll();
+        let view = self.latest.borrow_mut().take()?;
+        show_deadline(&view, self.side);
+        if let Some(outcome) = view.outcome.as_ref().filter(|_| !self.ended) {
+            // Nothing changes after the end, so there's no more asking.
+            self.ended = true;
+            // Games won on the board end on it too.
+            if outcome.termination == Termination::Forfeit {
+                let (title, _) =
+                    status::describe_result(outcome.winner, &outcome.termination, self.side);
+                let reason = if view.to_move() == self.side {
+                    "You ran out of time for your move."
+                } else {
+                    "Your opponent ran out of time for their move."
+                };
+                status::show_result(&title, reason, false);
+                a11y::announce(&format!("{} {}", title, reason));
+            }
+        }
+        if self.ply % self.players == self.side as usize {
+            return None;
+        }
+        view.moves.get(self.ply).cloned()
+    }
+}
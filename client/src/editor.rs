@@ -0,0 +1,334 @@
+//! The board editor, where pawns and walls are placed freely. The position can
+//! be shared as a position string, analysed, or played from in this browser.
+
+use crate::{
+    ai, fit_canvas, get_coords_from_event, local, render_board, set_colors, status, ColorStruct,
+    Layout, State,
+};
+use common::{
+    board::{Board, BOARD_SIZE, BOARD_SIZES},
+    position::setup_moves,
+};
+use quoridor_core::*;
+use std::{cell::RefCell, rc::Rc};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::theme::Settings;
+
+/// How many plies the AI looks ahead when analysing.
+const ANALYSIS_DEPTH: u8 = 3;
+
+struct Editor {
+    board: Board,
+    /// The start of a game on the same board, which games are set up from.
+    start: Board,
+    layout: Layout,
+    context: web_sys::CanvasRenderingContext2d,
+    worker: Option<ai::Worker>,
+    /// The position an analysis was asked for, until the AI answers.
+    asked: Option<Board>,
+    /// The position the AI is thinking about.
+    thinking: Option<Board>,
+}
+
+fn control<T: JsCast>(id: &str) -> Option<T> {
+    web_sys::window()?
+        .document()?
+        .get_element_by_id(id)?
+        .dyn_into::<T>()
+        .ok()
+}
+
+fn set_message(text: &str) {
+    if let Some(message) = control::<web_sys::HtmlElement>("editor-message") {
+        message.set_text_content(Some(text));
+    }
+}
+
+/// Adds a wall at `position`, turns it, or takes it away again. Taps on an
+/// intersection go through vertical, horizontal and no wall in turn.
+fn toggle_wall(board: &mut Board, position: Position, orientation: Option<Orientation>) {
+    let current = board
+        .walls
+        .iter()
+        .position(|w| w.position == position)
+        .map(|i| board.walls.remove(i).orientation);
+    let next = match (current, orientation) {
+        (None, Some(orientation)) => Some(orientation),
+        (None, None) => Some(Orientation::Vertical),
+        (Some(Orientation::Vertical), None) => Some(Orientation::Horizontal),
+        (Some(current), Some(orientation)) if current != orientation => Some(orientation),
+        _ => None,
+    };
+    if let Some(orientation) = next {
+        let wall = Wall {
+            position,
+            orientation,
+            wall_type: WallType::Simple,
+        };
+        if board.wall_fits(&wall) {
+            board.walls.push(wall);
+        }
+    }
+}
+
+impl Editor {
+    fn set_board(&mut self, board: Board) {
+        if board.size != self.board.size {
+            if let Some(start) = local::start_position(board.size) {
+                self.start = start;
+            }
+            self.layout = Layout::new(board.size, 0);
+        }
+        self.board = board;
+        self.changed();
+    }
+
+    /// Redraws the board and brings the controls in line with it.
+    fn changed(&mut self) {
+        self.asked = None;
+        set_message("");
+        render_board(&self.context, &self.board, &State::default(), &self.layout);
+
+        if let Some(turn) = control::<web_sys::HtmlSelectElement>("editor-turn") {
+            turn.set_value(&self.board.turn.to_string());
+        }
+        for (player, walls) in self.board.walls_left.iter().enumerate() {
+            if let Some(input) =
+                control::<web_sys::HtmlInputElement>(&format!("editor-walls-{}", player))
+            {
+                input.set_value(&walls.to_string());
+            }
+        }
+        if let Some(position) = control::<web_sys::HtmlInputElement>("editor-position") {
+            position.set_value(&self.board.to_string());
+        }
+    }
+
+    fn on_pointer_down(&mut self, event: &web_sys::PointerEvent) {
+        let (x, y) = get_coords_from_event(event, self.layout.side);
+        let (column, row, spot_x, spot_y) = self.layout.locate(x, y);
+        let size = self.layout.size;
+        if spot_x && spot_y {
+            if column >= size || row >= size {
+                return;
+            }
+            let pos = Position::from((column, size - 1 - row));
+            let pawn = control::<web_sys::HtmlSelectElement>("editor-pawn")
+                .and_then(|s| s.value().parse::<usize>().ok())
+                .filter(|&p| p < self.board.pawns.len())
+                .unwrap_or(0);
+            if self.board.pawns.contains(&pos) {
+                return;
+            }
+            self.board.pawns[pawn] = pos;
+        } else if let Some((position, orientation)) = self.layout.touch_target(x, y) {
+            toggle_wall(&mut self.board, position, orientation);
+        } else {
+            return;
+        }
+        self.changed();
+    }
+
+    fn read_controls(&mut self) {
+        if let Some(turn) = control::<web_sys::HtmlSelectElement>("editor-turn") {
+            self.board.turn = turn.value().parse().unwrap_or(self.board.turn);
+        }
+        for player in 0..self.board.walls_left.len() {
+            if let Some(input) =
+                control::<web_sys::HtmlInputElement>(&format!("editor-walls-{}", player))
+            {
+                if let Ok(walls) = input.value().parse() {
+                    self.board.walls_left[player] = walls;
+                }
+            }
+        }
+        self.changed();
+    }
+
+    fn load(&mut self) {
+        let text = control::<web_sys::HtmlInputElement>("editor-position").map(|p| p.value());
+        match text.unwrap_or_default().parse::<Board>() {
+            Ok(board) => self.set_board(board),
+            Err(e) => set_message(&format!("Can't read that position: {}", e)),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.set_board(self.start.clone());
+    }
+
+    /// Whether the position is one a game can go on from, or why not.
+    fn check(&self) -> Result<(), String> {
+        let board: Board = self
+            .board
+            .to_string()
+            .parse()
+            .map_err(|e| format!("{}", e))?;
+        match board.winner() {
+            Some(winner) => Err(format!("Player {} has already won.", winner)),
+            None => Ok(()),
+        }
+    }
+
+    fn analyse(&mut self) {
+        if let Err(e) = self.check() {
+            set_message(&e);
+            return;
+        }
+        let distance = |p| self.board.distance(p).unwrap_or_default();
+        set_message(&format!(
+            "Player 0 is {} steps from their goal, player 1 is {}. Thinking…",
+            distance(0),
+            distance(1)
+        ));
+        self.asked = Some(self.board.clone());
+    }
+
+    /// Passes analyses to the AI and shows its answers.
+    fn poll(&mut self) {
+        let worker = match self.worker.as_mut() {
+            Some(worker) => worker,
+            None => return,
+        };
+        if let Some(reply) = worker.reply() {
+            let board = self.thinking.take();
+            // Answers for positions edited since are dropped.
+            if board.is_some() && board == self.asked {
+                self.asked = None;
+                match reply {
+                    Some(mv) => {
+                        set_message(&format!("Best move for player {}: {}", self.board.turn, mv))
+                    }
+                    None => set_message(&format!("No move found for player {}.", self.board.turn)),
+                }
+            }
+        }
+        if let (Some(asked), None) = (&self.asked, &self.thinking) {
+            if worker.think(asked, ANALYSIS_DEPTH) {
+                self.thinking = Some(asked.clone());
+            }
+        }
+    }
+
+    /// Opens a game from this position in the page, with `query` choosing how.
+    fn play(&self, query: &str) {
+        if let Err(e) = self.check() {
+            set_message(&e);
+            return;
+        }
+        if setup_moves(&self.start, &self.board).is_none() {
+            set_message(
+                "That position can't come up in a game. Pawns move one square at a time, \
+                 so try giving the move to the other player.",
+            );
+            return;
+        }
+        let position = String::from(js_sys::encode_uri_component(&self.board.to_string()));
+        let url = format!("/game/standard/local?{}&position={}", query, position);
+        if let Some(window) = web_sys::window() {
+            let _ = window.location().set_href(&url);
+        }
+    }
+}
+
+/// Calls `handler` on the editor when the control `id` fires `event`.
+fn listen(
+    editor: &Rc<RefCell<Editor>>,
+    id: &str,
+    event: &str,
+    handler: fn(&mut Editor),
+) -> Option<()> {
+    let target = control::<web_sys::EventTarget>(id)?;
+    let editor = Rc::clone(editor);
+    let closure = Closure::wrap(Box::new(move || {
+        handler(&mut editor.borrow_mut());
+    }) as Box<dyn FnMut()>);
+    target
+        .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())
+        .ok()?;
+    closure.forget();
+    Some(())
+}
+
+/// Opens the editor on the position in the page query, or on the start of a
+/// game of the size it asks for.
+pub fn start(
+    search: &str,
+    context: web_sys::CanvasRenderingContext2d,
+    canvas: web_sys::HtmlCanvasElement,
+) -> Option<()> {
+    let size = local::query_param(search, "size")
+        .and_then(|s| s.parse().ok())
+        .filter(|s| BOARD_SIZES.contains(s))
+        .unwrap_or(BOARD_SIZE);
+    let board = local::query_param(search, "position")
+        .and_then(local::decode)
+        .and_then(|p| p.parse::<Board>().ok());
+    let size = board.as_ref().map_or(size, |b| b.size);
+    let start = local::start_position(size)?;
+
+    set_colors(ColorStruct::new(&Settings::load(), start.pawns.len() as u8));
+    status::set_connection(status::Connection::Editor);
+    control::<web_sys::HtmlElement>("editor")?
+        .remove_attribute("hidden")
+        .ok()?;
+    canvas
+        .set_attribute("aria-label", "Quoridor board editor")
+        .ok()?;
+
+    let editor = Rc::new(RefCell::new(Editor {
+        board: board.unwrap_or_else(|| start.clone()),
+        start,
+        layout: Layout::new(size, 0),
+        context,
+        worker: ai::Worker::new(),
+        asked: None,
+        thinking: None,
+    }));
+    fit_canvas(&canvas);
+    editor.borrow_mut().changed();
+
+    let rcc = Rc::clone(&editor);
+    let on_pointer_down = Closure::wrap(Box::new(move |event: web_sys::PointerEvent| {
+        rcc.borrow_mut().on_pointer_down(&event);
+    }) as Box<dyn FnMut(_)>);
+    canvas.set_onpointerdown(Some(on_pointer_down.as_ref().unchecked_ref()));
+    on_pointer_down.forget();
+
+    listen(&editor, "editor-turn", "change", Editor::read_controls);
+    listen(&editor, "editor-walls-0", "input", Editor::read_controls);
+    listen(&editor, "editor-walls-1", "input", Editor::read_controls);
+    listen(&editor, "editor-load", "click", Editor::load);
+    listen(&editor, "editor-reset", "click", Editor::reset);
+    listen(&editor, "editor-analyse", "click", Editor::analyse);
+    listen(&editor, "editor-hotseat", "click", |e| {
+        e.play("mode=hotseat")
+    });
+    listen(&editor, "editor-ai", "click", |e| e.play("mode=ai"));
+
+    let rcc = Rc::clone(&editor);
+    let on_resize = Closure::wrap(Box::new(move || {
+        let editor = rcc.borrow();
+        fit_canvas(&canvas);
+        render_board(
+            &editor.context,
+            &editor.board,
+            &State::default(),
+            &editor.layout,
+        );
+    }) as Box<dyn FnMut()>);
+    let window = web_sys::window()?;
+    window
+        .add_event_listener_with_callback("resize", on_resize.as_ref().unchecked_ref())
+        .ok()?;
+    on_resize.forget();
+
+    let poll = Closure::wrap(Box::new(move || editor.borrow_mut().poll()) as Box<dyn FnMut()>);
+    window
+        .set_interval_with_callback_and_timeout_and_arguments_0(poll.as_ref().unchecked_ref(), 100)
+        .ok()?;
+    poll.forget();
+    Some(())
+}
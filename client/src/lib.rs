@@ -7,6 +7,24 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{MessageEvent, WebSocket};
 
+mod a11y;
+mod ai;
+mod animation;
+mod correspondence;
+mod editor;
+mod local;
+mod puzzle;
+mod status;
+mod theme;
+
+use common::{
+    board::{Board, GameSize},
+    notation::{format_move, format_square, format_wall},
+    record::Termination,
+};
+
+use theme::{Settings, Shape};
+
 #[allow(unused_macros)]
 macro_rules! console_log {
     ($($t:tt)*) => ( #[allow(unused_unsafe)]unsafe { log(&format_args!($($t)*).to_string()) })
@@ -31,20 +49,111 @@ generate_rulebook! {
 
 const STANDARD_CANVAS_SIZE: f64 = 150.0;
 const WALL_TO_SPOT_RATIO: f64 = 2.5;
-const WALL_WIDTH: f64 = STANDARD_CANVAS_SIZE / (10.0 + WALL_TO_SPOT_RATIO * 9.0);
-const SPOT_WIDTH: f64 = WALL_WIDTH * WALL_TO_SPOT_RATIO;
-const UNIT_WIDTH: f64 = WALL_WIDTH + SPOT_WIDTH;
 
-thread_local! {
-    static COLORS: RefCell<ColorStruct> = RefCell::new(
-        ColorStruct {
-            base: JsValue::from_str("#50190A"),
-            wall_slot: JsValue::from_str("#743c0d"),
-            wall: JsValue::from_str("#996F38"),
-            select: JsValue::from_str("#ACACAC"),
-            pawns: vec![]
+/// Board geometry in canvas units, derived from the board size the game started with.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    size: u8,
+    /// The side playing here; the board is drawn upside down for player 1.
+    side: PlayerID,
+    wall_width: f64,
+    spot_width: f64,
+    unit_width: f64,
+}
+
+impl Layout {
+    fn flipped(&self) -> bool {
+        self.side == 1
+    }
+
+    fn new(size: u8, side: PlayerID) -> Self {
+        let size_f = size as f64;
+        let wall_width = STANDARD_CANVAS_SIZE / (size_f + 1.0 + WALL_TO_SPOT_RATIO * size_f);
+        let spot_width = wall_width * WALL_TO_SPOT_RATIO;
+        Layout {
+            size,
+            side,
+            wall_width,
+            spot_width,
+            unit_width: wall_width + spot_width,
+        }
+    }
+
+    /// Finds the grid cell under a point, counted from the top left, and
+    /// whether the point is past that cell's wall slot on each axis.
+    fn locate(&self, x: f64, y: f64) -> (u8, u8, bool, bool) {
+        let mod_x = x % self.unit_width;
+        let mod_y = y % self.unit_width;
+        (
+            ((x - mod_x) / self.unit_width) as u8,
+            ((y - mod_y) / self.unit_width) as u8,
+            mod_x > self.wall_width,
+            mod_y > self.wall_width,
+        )
+    }
+
+    /// The rectangle a simple wall covers, as `(x, y, width, height)`.
+    fn wall_rect(&self, wall: &Wall) -> (f64, f64, f64, f64) {
+        let h_control = (wall.orientation == Orientation::Horizontal) as u8 as f64;
+        let v_conrol = (wall.orientation == Orientation::Vertical) as u8 as f64;
+        let length = self.unit_width + self.spot_width;
+        (
+            wall.position.x as f64 * self.unit_width - h_control * self.spot_width,
+            (self.size - wall.position.y) as f64 * self.unit_width - v_conrol * self.spot_width,
+            self.wall_width * v_conrol + length * h_control,
+            self.wall_width * h_control + length * v_conrol,
+        )
+    }
+
+    /// The centre of the intersection a wall is placed on.
+    fn wall_centre(&self, wall: &Wall) -> (f64, f64) {
+        let half = self.wall_width / 2.0;
+        (
+            wall.position.x as f64 * self.unit_width + half,
+            (self.size - wall.position.y) as f64 * self.unit_width + half,
+        )
+    }
+
+    /// Finds the wall slot under a touch, with hit zones twice as wide as the
+    /// slots themselves so they can be hit with a finger. Returns the nearest
+    /// wall position and, unless the touch is on an intersection, the
+    /// orientation of the slot.
+    fn touch_target(&self, x: f64, y: f64) -> Option<(Position, Option<Orientation>)> {
+        let half = self.wall_width / 2.0;
+        let line = |v: f64| {
+            let i = ((v - half) / self.unit_width).round();
+            let near = (v - (i * self.unit_width + half)).abs() < self.wall_width;
+            // Walls are centred on inner intersections only.
+            let i = f64::min(f64::max(i, 1.0), (self.size - 1) as f64) as u8;
+            (i, near)
+        };
+        let ((i, near_v), (j, near_h)) = (line(x), line(y));
+        let position = Position::from((i, self.size - j));
+        match (near_v, near_h) {
+            (true, true) => Some((position, None)),
+            (true, false) => Some((position, Some(Orientation::Vertical))),
+            (false, true) => Some((position, Some(Orientation::Horizontal))),
+            (false, false) => None,
         }
-    );
+    }
+
+    /// Whether a touch lands on a wall, again with a widened hit zone.
+    fn wall_contains(&self, wall: &Wall, x: f64, y: f64) -> bool {
+        let (left, top, width, height) = self.wall_rect(wall);
+        let pad = self.wall_width / 2.0;
+        x >= left - pad && x <= left + width + pad && y >= top - pad && y <= top + height + pad
+    }
+}
+
+fn rotate(orientation: Orientation) -> Orientation {
+    match orientation {
+        Orientation::Horizontal => Orientation::Vertical,
+        Orientation::Vertical => Orientation::Horizontal,
+    }
+}
+
+thread_local! {
+    static COLORS: RefCell<ColorStruct> = RefCell::new(ColorStruct::new(&Settings::load(), 0));
 }
 
 fn get_colors() -> ColorStruct {
@@ -71,6 +180,50 @@ fn set_colors(new_colors: ColorStruct) {
 struct State {
     highlight: Option<Position>,
     draw_start: Option<Wall>,
+    /// A wall placed by touch, shown until it is confirmed with another tap.
+    ghost: Option<Wall>,
+    /// Whether a touch that moves should drag the ghost along.
+    dragging: bool,
+    /// The square the keyboard cursor is on, once the keyboard has been used.
+    cursor: Option<Position>,
+    /// The last move played, which is highlighted.
+    last_move: Option<Move>,
+    /// When the last move started animating, while it still is.
+    animation_start: Option<f64>,
+}
+
+type Shared = Rc<(
+    RefCell<Quoridor>,
+    RefCell<web_sys::CanvasRenderingContext2d>,
+    RefCell<State>,
+    RefCell<PlayerID>,
+    RefCell<QAgent>,
+    RefCell<web_sys::HtmlElement>,
+    RefCell<Layout>,
+)>;
+
+/// Redraws the board every frame until the last move has finished animating.
+fn animate(shared: Shared) {
+    let frame: Rc<RefCell<Option<Closure<dyn FnMut()>>>> = Rc::new(RefCell::new(None));
+    let next = frame.clone();
+    *frame.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+        let mut state = shared.2.borrow_mut();
+        render_game(
+            &shared.1.borrow(),
+            &shared.5.borrow(),
+            &shared.0.borrow(),
+            &state,
+            &shared.6.borrow(),
+        );
+        if animation::progress(state.animation_start) < 1.0 {
+            animation::request_frame(next.borrow().as_ref().unwrap());
+        } else {
+            state.animation_start = None;
+            // Drops this closure, ending the loop.
+            let _ = next.borrow_mut().take();
+        }
+    }) as Box<dyn FnMut()>));
+    animation::request_frame(frame.borrow().as_ref().unwrap());
 }
 
 #[derive(Clone)]
@@ -80,31 +233,41 @@ struct ColorStruct {
     wall: JsValue,
     select: JsValue,
     pawns: Vec<JsValue>,
+    shapes: Vec<Shape>,
+}
+
+impl ColorStruct {
+    fn new(settings: &Settings, pawn_count: u8) -> Self {
+        let theme = settings.theme;
+        ColorStruct {
+            base: JsValue::from_str(theme.base),
+            wall_slot: JsValue::from_str(theme.wall_slot),
+            wall: JsValue::from_str(theme.wall),
+            select: JsValue::from_str(theme.select),
+            pawns: settings.pawn_colors(pawn_count),
+            shapes: settings.pawn_shapes(pawn_count),
+        }
+    }
 }
 
 #[wasm_bindgen(start)]
 pub fn start() {
+    // The AI's worker loads this module too, with no page to run.
+    if web_sys::window().is_none() {
+        return;
+    }
     main().unwrap();
 }
 
 fn main() -> Option<()> {
     let document = web_sys::window()?.document()?;
+    theme::init_controls(&document, &Settings::load());
 
     let canvas = document.create_element("canvas").unwrap();
     let main_div = document.get_element_by_id("divvv").unwrap();
 
     main_div.append_child(&canvas).unwrap();
 
-    let div = main_div
-        .dyn_into::<web_sys::HtmlElement>()
-        .ok()?
-        .get_bounding_client_rect();
-
-    let width = web_sys::window()?.inner_width().ok()?.as_f64()? - div.left();
-    let height = web_sys::window()?.inner_height().ok()?.as_f64()? - div.top();
-
-    let size = f64::min(width, height);
-
     let data_div = document
         .get_element_by_id("metadata")?
         .dyn_into::<web_sys::HtmlElement>()
@@ -113,37 +276,22 @@ fn main() -> Option<()> {
     let canvas: web_sys::HtmlCanvasElement =
         canvas.dyn_into::<web_sys::HtmlCanvasElement>().ok()?;
 
-    canvas.set_width(size as u32);
-    canvas.set_height(size as u32);
-
-    if width > height {
-        let margin = (width - size) / 2.0;
-        canvas
-            .style()
-            .set_property("margin-left", &format!("{}px", margin))
-            .ok()?;
-        canvas
-            .style()
-            .set_property("margin-right", &format!("{}px", margin))
-            .ok()?;
-    } else {
-        let margin = (height - size) / 2.0;
-        canvas
-            .style()
-            .set_property("margin-top", &format!("{}px", margin))
-            .ok()?;
-        canvas
-            .style()
-            .set_property("margin-bottom", &format!("{}px", margin))
-            .ok()?;
-    }
+    fit_canvas(&canvas)?;
+    // Focusable so the board can be played from the keyboard, see `on_key`.
+    canvas.set_tab_index(0);
+    canvas.set_attribute("role", "application").ok()?;
+    canvas
+        .set_attribute("aria-label", "Quoridor board, press d to hear the position")
+        .ok()?;
+    canvas.set_attribute("aria-describedby", "description help").ok()?;
 
     let location = web_sys::window()?.location();
-    let href: String = location.href().ok()?;
-    let keys: Vec<_> = href.split('/').rev().filter(|s| !s.is_empty()).collect();
+    let path: String = location.pathname().ok()?;
+    let keys: Vec<_> = path.split('/').rev().filter(|s| !s.is_empty()).collect();
     let game_name = keys[0];
     let host = location.host().ok()?;
-    let mut ws = WebSocket::new(&format!("ws://{}/join/{}", host, game_name)).ok()?;
+    // Pass the query on so links can ask for a seat, e.g. `?side=1`.
+    let mut search = location.search().ok()?;
 
     let context = canvas
         .get_context("2d")
@@ -151,6 +299,50 @@ fn main() -> Option<()> {
         .dyn_into::<web_sys::CanvasRenderingContext2d>()
         .ok()?;
 
+    if let Some(id) = local::puzzle_from_query(&search) {
+        return puzzle::fetch(&id, move |puzzle| {
+            if let Some((local, agent, game)) = local::LocalGame::start_puzzle(&puzzle) {
+                a11y::announce(&puzzle.title);
+                let side = local.side(game.turn_of(), &game);
+                on_connect(agent, game, side, context, data_div, canvas, Some(local));
+            }
+        });
+    }
+
+    if let Some((id, token)) = correspondence::from_query(&search) {
+        let seat = token.clone();
+        return correspondence::fetch(&id, &token, move |view| {
+            match local::LocalGame::start_correspondence(&view, seat) {
+                Ok((local, agent, game)) => {
+                    let side = local.side(game.turn_of(), &game);
+                    on_connect(agent, game, side, context, data_div, canvas, Some(local));
+                }
+                Err(e) => status::show_result("Can't open the game", &e, false),
+            }
+        });
+    }
+
+    if local::query_param(&search, "mode") == Some("editor") {
+        return editor::start(&search, context, canvas);
+    }
+
+    if let Some(options) = local::Options::from_query(keys[1], &search) {
+        start_local(&options, context, data_div, canvas);
+        return Some(());
+    }
+
+    // The lobby page remembers the player's nickname.
+    let nickname = web_sys::window()?
+        .local_storage()
+        .ok()?
+        .and_then(|s| s.get_item("nickname").ok()?);
+    if let Some(nickname) = nickname.filter(|_| !search.contains("nick=")) {
+        search.push(if search.is_empty() { '?' } else { '&' });
+        search.push_str("nick=");
+        search.push_str(&String::from(js_sys::encode_uri_component(&nickname)));
+    }
+    let mut ws = WebSocket::new(&format!("ws://{}/join/{}{}", host, game_name, search)).ok()?;
+
     let agent = match &keys[1][..] {
         "free" => QAgent::FreeQuoridor(WSAgent::<QGame<FreeQuoridor>>::connect(&mut ws)),
         "standard" => {
@@ -159,15 +351,39 @@ fn main() -> Option<()> {
         _ => panic!(),
     };
 
+    status::set_connection(status::Connection::Connecting);
     let ocnt = Closure::once(move || {
         console_log!("connection ready!");
+        status::set_connection(status::Connection::Waiting);
     });
+    let offline = {
+        let (context, data_div, canvas) = (context.clone(), data_div.clone(), canvas.clone());
+        let options = local::Options::from_query(keys[1], "?mode=ai");
+        move || {
+            if let Some(options) = options {
+                start_local(&options, context, data_div, canvas);
+            }
+        }
+    };
+    let mut offline = Some(offline);
+    let on_close = Closure::wrap(Box::new(move || {
+        // Never got as far as a game, so offer one without the server.
+        let connection = status::connection();
+        if connection == status::Connection::Connecting || connection == status::Connection::Waiting {
+            if let Some(offline) = offline.take() {
+                status::offer_offline(offline);
+            }
+        }
+        status::set_connection(status::Connection::Disconnected);
+    }) as Box<dyn FnMut()>);
+    ws.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+    on_close.forget();
+    status::init_rematch(ws.clone());
 
     fn rec(
         agent: QAgent,
         context: web_sys::CanvasRenderingContext2d,
         div: web_sys::HtmlElement,
-        size: f64,
         canvas: web_sys::HtmlCanvasElement,
     ) {
         console_log!("lööps");
@@ -176,19 +392,11 @@ fn main() -> Option<()> {
                 QGameEvent::GameStart(g, s) => (g, s),
                 _ => unreachable!(),
             };
-            let scale = size as f64 / STANDARD_CANVAS_SIZE;
-
-            if side == 1 {
-                context.translate(0.0, size).unwrap();
-                context.scale(scale, -scale).unwrap();
-            } else {
-                context.scale(scale, scale).unwrap();
-            };
-            on_connect(agent, game, side, context, div, size, canvas)
-        } else {
-            //rec(agent, context, side, size, canvas);
+            on_connect(agent, game, side, context, div, canvas, None)
+        } else if status::connection() != status::Connection::Disconnected {
+            //rec(agent, context, side, canvas);
             let r = Closure::once(move || {
-                rec(agent, context, div, size, canvas);
+                rec(agent, context, div, canvas);
             });
             web_sys::window()
                 .unwrap()
@@ -201,14 +409,58 @@ fn main() -> Option<()> {
         }
     }
 
-    rec(agent, context, data_div, size, canvas);
+    rec(agent, context, data_div, canvas);
 
     ws.set_onopen(Some(ocnt.as_ref().unchecked_ref()));
     ocnt.forget();
     Some(())
 }
 
-fn  get_coords_from_event(e: &web_sys::PointerEvent, side: PlayerID) -> (f64, f64) {
+/// Starts a game in this browser, or says why it couldn't be.
+fn start_local(
+    options: &local::Options,
+    context: web_sys::CanvasRenderingContext2d,
+    data_div: web_sys::HtmlElement,
+    canvas: web_sys::HtmlCanvasElement,
+) {
+    match local::LocalGame::start(options) {
+        Ok((local, agent, game)) => {
+            let side = local.side(game.turn_of(), &game);
+            on_connect(agent, game, side, context, data_div, canvas, Some(local));
+        }
+        Err(e) => status::show_result("Can't start the game", &e, false),
+    }
+}
+
+/// Sizes the canvas to the largest square that fits below its container's top
+/// left corner, centred, with a backing store scaled by `devicePixelRatio` so
+/// the board stays sharp on high-DPI screens.
+fn fit_canvas(canvas: &web_sys::HtmlCanvasElement) -> Option<()> {
+    let window = web_sys::window()?;
+    let div = canvas.parent_element()?.get_bounding_client_rect();
+
+    let width = window.inner_width().ok()?.as_f64()? - div.left();
+    let height = window.inner_height().ok()?.as_f64()? - div.top();
+    let size = f64::max(f64::min(width, height), 0.0);
+    let (margin_x, margin_y) = ((width - size) / 2.0, (height - size) / 2.0);
+
+    let style = canvas.style();
+    style.set_property("width", &format!("{}px", size)).ok()?;
+    style.set_property("height", &format!("{}px", size)).ok()?;
+    style.set_property("margin-left", &format!("{}px", margin_x)).ok()?;
+    style.set_property("margin-right", &format!("{}px", margin_x)).ok()?;
+    style.set_property("margin-top", &format!("{}px", margin_y)).ok()?;
+    style.set_property("margin-bottom", &format!("{}px", margin_y)).ok()?;
+
+    let pixels = (size * window.device_pixel_ratio()).round() as u32;
+    canvas.set_width(pixels);
+    canvas.set_height(pixels);
+    Some(())
+}
+
+/// Converts an event's position to board units, counted from the top left
+/// corner of the board as `side` sees it.
+fn get_coords_from_event(e: &web_sys::PointerEvent, side: PlayerID) -> (f64, f64) {
     // e = Mouse click event.
     let rect = e
         .target()
@@ -216,12 +468,14 @@ fn  get_coords_from_event(e: &web_sys::PointerEvent, side: PlayerID) -> (f64, f6
         .dyn_into::<web_sys::HtmlElement>()
         .unwrap()
         .get_bounding_client_rect();
-    let x = e.client_x() as f64 - rect.left(); //x position within the element.
-    let y = e.client_y() as f64 - rect.top(); //y position within the element.
+    // Measured from the element every time, so hit-testing follows resizes.
+    let scale = STANDARD_CANVAS_SIZE / rect.width();
+    let x = (e.client_x() as f64 - rect.left()) * scale; //x position within the element.
+    let y = (e.client_y() as f64 - rect.top()) * scale; //y position within the element.
     if side == 0 {
         (x, y)
     } else {
-        (x, rect.height() as f64 - y)
+        (x, STANDARD_CANVAS_SIZE - y)
     }
 }
 
@@ -231,33 +485,39 @@ fn on_connect(
     side: PlayerID,
     context: web_sys::CanvasRenderingContext2d,
     data_div: web_sys::HtmlElement,
-    size: f64,
     canvas: web_sys::HtmlCanvasElement,
+    local: Option<local::LocalGame>,
 ) {
-    let mut colors = get_colors();
-    for i in 0..game.get_pawn_count() {
-        let color = format!(
-            "hsl({},100%,50%)",
-            i as f64 * 360.0 / game.get_pawn_count() as f64
-        );
-        colors.pawns.push(JsValue::from_str(&color));
-    }
-    set_colors(colors);
+    set_colors(ColorStruct::new(&Settings::load(), game.get_pawn_count()));
+    status::set_connection(match &local {
+        Some(l) if l.hot_seat() => status::Connection::Local,
+        Some(l) if l.puzzle() => status::Connection::Puzzle,
+        Some(l) if l.correspondence() => status::Connection::Correspondence,
+        Some(_) => status::Connection::Computer,
+        None => status::Connection::Playing,
+    });
 
+    let viewer = local.as_ref().map_or(side, |l| l.viewer(side, &game));
+    let layout = Layout::new(game.size(), viewer);
     let state = State::default();
-    render_game(&context, &data_div, &game, &state);
+    // The window may have changed while waiting for the opponent.
+    fit_canvas(&canvas);
+    render_game(&context, &data_div, &game, &state, &layout);
+    a11y::update_description(&game, side);
 
-    let rc = Rc::new((
+    let rc: Shared = Rc::new((
         RefCell::new(game),
         RefCell::new(context),
         RefCell::new(state),
         RefCell::new(side),
         RefCell::new(agent),
         RefCell::new(data_div),
+        RefCell::new(layout),
     ));
 
     let rcc = Clone::clone(&rc);
     let on_mouse_down = move |event: web_sys::PointerEvent| {
+        let layout = *rcc.6.borrow();
         let game = rcc.0.borrow_mut();
         let context = rcc.1.borrow();
         let mut state = rcc.2.borrow_mut();
@@ -265,39 +525,26 @@ fn on_connect(
         let agent = rcc.4.borrow();
         let data_div = rcc.5.borrow();
 
-        let (offset_x, offset_y) = get_coords_from_event(&event, *side);
+        let (x, y) = get_coords_from_event(&event, layout.side);
 
-        let x = STANDARD_CANVAS_SIZE * offset_x as f64 / size;
-        let y = STANDARD_CANVAS_SIZE * offset_y as f64 / size;
-
-        let mod_x = x % UNIT_WIDTH;
-        let mod_y = y % UNIT_WIDTH;
+        if event.pointer_type() != "mouse" {
+            if on_touch_down(&game, &mut state, *side, &agent, &layout, x, y) {
+                render_game(&context, &data_div, &game, &state, &layout);
+                return;
+            }
+        }
 
-        let x = ((x - mod_x) / UNIT_WIDTH) as u8;
-        let y = ((y - mod_y) / UNIT_WIDTH) as u8;
+        let (x, y, spot_x, spot_y) = layout.locate(x, y);
 
-        match (mod_x > WALL_WIDTH, mod_y > WALL_WIDTH) {
+        match (spot_x, spot_y) {
             (true, true) => {
-                let pos = Position::from((x, 8 - y));
-                state.highlight = match (game.pawns().get_by_right(&pos), state.highlight) {
-                    (Some(id), _) if id.owned_by(&game) != *side => None,
-                    (Some(_), None) => Some(pos),
-                    (Some(_), Some(hpos)) if hpos != pos => Some(pos),
-                    (None, Some(hpos)) => {
-                        if game.turn_of() == *side {
-                            //send a move
-                            let qmv = Move::MovePawn(hpos, pos);
-                            agent.send_move(RulebookMove::wrap(&game, &qmv)).unwrap();
-                        }
-                        None
-                    }
-                    _ => None,
-                };
+                let pos = Position::from((x, layout.size - 1 - y));
+                select_square(&game, &mut state, *side, &agent, pos);
             }
             (false, false) => {
                 if event.pointer_type() == "mouse" {
                     let wall = Wall {
-                        position: (x, 9 - y).into(),
+                        position: (x, layout.size - y).into(),
                         orientation: if event.button() == 0 {
                             Orientation::Vertical
                         } else {
@@ -315,7 +562,7 @@ fn on_connect(
             }
             (horizontal, _vertical) => {
                 let w1 = Wall {
-                    position: (x, horizontal as u8 + 8 - y).into(),
+                    position: (x, horizontal as u8 + layout.size - 1 - y).into(),
                     orientation: if horizontal {
                         Orientation::Horizontal
                     } else {
@@ -326,64 +573,152 @@ fn on_connect(
                 state.draw_start = Some(w1);
             }
         }
-        render_game(&context, &data_div, &game, &state);
+        render_game(&context, &data_div, &game, &state, &layout);
     };
 
     let rcc = Clone::clone(&rc);
+    let on_pointer_move = move |event: web_sys::PointerEvent| {
+        let layout = *rcc.6.borrow();
+        let mut state = rcc.2.borrow_mut();
+        if event.pointer_type() == "mouse" || !state.dragging {
+            return;
+        }
+        let (x, y) = get_coords_from_event(&event, layout.side);
+        if let (Some(ghost), Some((position, _))) = (state.ghost, layout.touch_target(x, y)) {
+            if ghost.position != position {
+                state.ghost = Some(Wall { position, ..ghost });
+                let game = rcc.0.borrow();
+                render_game(&rcc.1.borrow(), &rcc.5.borrow(), &game, &state, &layout);
+            }
+        }
+    };
+
+    let rcc = Clone::clone(&rc);
+    let mut local = local;
     let game_event_handler = move || {
         let mut game = rcc.0.borrow_mut();
         let context = rcc.1.borrow_mut();
-        let state = rcc.2.borrow();
-        //let side = rcc.3.borrow();
-        let agent = rcc.4.borrow();
+        let mut state = rcc.2.borrow_mut();
+        let mut side = rcc.3.borrow_mut();
+        let mut agent = rcc.4.borrow_mut();
         let div = rcc.5.borrow();
+        let mut layout = rcc.6.borrow_mut();
+
+        let hot_seat = local.as_ref().map_or(false, |l| l.hot_seat());
+        let puzzle = local.as_ref().map_or(false, |l| l.puzzle());
+        if let Some(local) = local.as_mut() {
+            if let Err(e) = local.play(&agent) {
+                a11y::announce(&format!("That move isn't allowed: {}", e));
+            }
+        }
 
-        if let Ok(e) = agent.recv_event() {
+        while let Ok(e) = agent.recv_event() {
             match e {
+                // Only seen again for a rematch, on the same connection.
+                QGameEvent::GameStart(g, s) => {
+                    *game = g;
+                    *side = s;
+                    *state = State::default();
+                    *layout = Layout::new(game.size(), s);
+                    set_colors(ColorStruct::new(&Settings::load(), game.get_pawn_count()));
+                    status::hide_result();
+                    a11y::announce(&format!("Rematch started, you are player {}", s));
+                    a11y::update_description(&game, s);
+                }
                 QGameEvent::MoveHappened(qmv) => {
+                    let player = game.turn_of();
+                    if let Some(verdict) = local.as_mut().and_then(|l| l.observe(&game, &qmv)) {
+                        verdict.show();
+                    }
                     game.apply_move(&qmv);
+                    state.last_move = Some(qmv);
+                    if !animation::reduced_motion() {
+                        let animating = state.animation_start.is_some();
+                        state.animation_start = Some(animation::now());
+                        // A loop already running picks the new move up.
+                        if !animating {
+                            animate(Rc::clone(&rcc));
+                        }
+                    }
+                    if player != *side || hot_seat {
+                        a11y::announce(&format!("Player {} played {}", player, format_move(&qmv)));
+                    }
+                    a11y::update_description(&game, *side);
+                }
+                // The puzzle's verdict says how it went.
+                QGameEvent::GameEnd(_) if puzzle => {}
+                QGameEvent::GameEnd(pid) if hot_seat => {
+                    let title = match pid {
+                        Some(w) => format!("Player {} won!", w),
+                        None => String::from("Draw"),
+                    };
+                    status::show_result(&title, "Reload the page to play again.", false);
+                    a11y::announce(&title);
                 }
                 QGameEvent::GameEnd(pid) => {
-                    if let Some(id) = pid {
-                        alert!("Player {} won!", id);
-                    } else {
-                        alert!("Draw!");
-                    }
+                    let (title, reason) = status::describe_result(pid, &Termination::Goal, *side);
+                    status::show_result(&title, &reason, local.is_none());
+                    a11y::announce(&format!("{} {}", title, reason));
                 }
                 QGameEvent::OpponentQuit => {
-                    alert!("Opponent quit!");
+                    let (title, _) =
+                        status::describe_result(Some(*side), &Termination::Forfeit, *side);
+                    status::show_result(&title, "Your opponent left the game.", false);
+                    a11y::announce("Your opponent left the game.");
                 }
                 _ => {}
             }
-            render_game(&context, &div, &game, &state);
+            render_game(&context, &div, &game, &state, &layout);
+        }
+
+        match local.as_mut() {
+            // Pass the board on once the move is played out.
+            Some(local) if local.hot_seat() => {
+                let to_move = game.turn_of();
+                if to_move != *side {
+                    local.hand_over(&mut agent, *side, to_move);
+                    *side = to_move;
+                    *layout = Layout::new(game.size(), local.viewer(to_move, &game));
+                    state.highlight = None;
+                    state.cursor = None;
+                    a11y::update_description(&game, to_move);
+                    render_game(&context, &div, &game, &state, &layout);
+                }
+            }
+            // The replies come back through the agent like anyone else's.
+            Some(local) => {
+                let played = local
+                    .play_ai(&game)
+                    .and_then(|_| local.play_puzzle(&game))
+                    .and_then(|_| local.play_correspondence(&game));
+                if let Err(e) = played {
+                    console_log!("the opponent's move was refused: {}", e);
+                }
+            }
+            None => {}
         }
     };
 
     let rcc = Clone::clone(&rc);
     let on_mouse_up = move |event: web_sys::PointerEvent| {
+        let layout = *rcc.6.borrow();
         let game = rcc.0.borrow_mut();
         //let context = rcc.1.borrow_mut();
-        let state = rcc.2.borrow();
+        let mut state = rcc.2.borrow_mut();
+        state.dragging = false;
         let side = rcc.3.borrow();
         let agent = rcc.4.borrow_mut();
 
-        let (offset_x, offset_y) = get_coords_from_event(&event, *side);
-
-        let x = STANDARD_CANVAS_SIZE * offset_x as f64 / size as f64;
-        let y = STANDARD_CANVAS_SIZE * offset_y as f64 / size as f64;
-
-        let mod_x = x % UNIT_WIDTH;
-        let mod_y = y % UNIT_WIDTH;
+        let (x, y) = get_coords_from_event(&event, layout.side);
 
-        let x = ((x - mod_x) / UNIT_WIDTH) as u8;
-        let y = ((y - mod_y) / UNIT_WIDTH) as u8;
+        let (x, y, spot_x, spot_y) = layout.locate(x, y);
 
-        match (mod_x > WALL_WIDTH, mod_y > WALL_WIDTH) {
+        match (spot_x, spot_y) {
             (true, true) | (false, false) => {}
             (horizontal, _vertical) => {
                 if let Some(w1) = state.draw_start {
                     let w2 = Wall {
-                        position: (x, horizontal as u8 + 8 - y).into(),
+                        position: (x, horizontal as u8 + layout.size - 1 - y).into(),
                         orientation: if horizontal {
                             Orientation::Horizontal
                         } else {
@@ -416,6 +751,31 @@ fn on_connect(
         }
     };
 
+    let rcc = Clone::clone(&rc);
+    theme::on_change(move |settings| {
+        let layout = *rcc.6.borrow();
+        let game = rcc.0.borrow();
+        set_colors(ColorStruct::new(settings, game.get_pawn_count()));
+        render_game(&rcc.1.borrow(), &rcc.5.borrow(), &game, &rcc.2.borrow(), &layout);
+    });
+
+    let rcc = Clone::clone(&rc);
+    let resize_canvas = canvas.clone();
+    let on_resize = move || {
+        let layout = *rcc.6.borrow();
+        fit_canvas(&resize_canvas);
+        render_game(&rcc.1.borrow(), &rcc.5.borrow(), &rcc.0.borrow(), &rcc.2.borrow(), &layout);
+    };
+
+    let closure = Closure::wrap(Box::new(on_resize) as Box<dyn FnMut()>);
+    let window = web_sys::window().unwrap();
+    for event in &["resize", "orientationchange"] {
+        window
+            .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())
+            .unwrap();
+    }
+    closure.forget();
+
     let closure = Closure::wrap(Box::new(game_event_handler) as Box<dyn FnMut()>);
     let window = web_sys::window().unwrap();
     window
@@ -433,6 +793,230 @@ fn on_connect(
     let closure = Closure::wrap(Box::new(on_mouse_up) as Box<dyn FnMut(web_sys::PointerEvent)>);
     canvas.set_onpointerup(Some(closure.as_ref().unchecked_ref()));
     closure.forget();
+
+    let closure =
+        Closure::wrap(Box::new(on_pointer_move) as Box<dyn FnMut(web_sys::PointerEvent)>);
+    canvas.set_onpointermove(Some(closure.as_ref().unchecked_ref()));
+    closure.forget();
+
+    let rcc = Clone::clone(&rc);
+    let on_key_down = move |event: web_sys::KeyboardEvent| {
+        let layout = *rcc.6.borrow();
+        let game = rcc.0.borrow();
+        let mut state = rcc.2.borrow_mut();
+        let side = rcc.3.borrow();
+        let agent = rcc.4.borrow();
+        if on_key(&game, &mut state, *side, &agent, &layout, &event.key()) {
+            // Keeps the arrow keys and space from scrolling the page.
+            event.prevent_default();
+            render_game(&rcc.1.borrow(), &rcc.5.borrow(), &game, &state, &layout);
+        }
+    };
+
+    let closure =
+        Closure::wrap(Box::new(on_key_down) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+    canvas.set_onkeydown(Some(closure.as_ref().unchecked_ref()));
+    closure.forget();
+}
+
+/// Selects one of `side`'s pawns, or moves the selected pawn to `pos`.
+fn select_square(game: &Quoridor, state: &mut State, side: PlayerID, agent: &QAgent, pos: Position) {
+    state.highlight = match (game.pawns().get_by_right(&pos), state.highlight) {
+        (Some(id), _) if id.owned_by(game) != side => None,
+        (Some(_), None) => Some(pos),
+        (Some(_), Some(hpos)) if hpos != pos => Some(pos),
+        (None, Some(hpos)) => {
+            let qmv = Move::MovePawn(hpos, pos);
+            if game.turn_of() == side && Board::from_game(game).is_legal(&qmv) {
+                //send a move
+                agent.send_move(RulebookMove::wrap(game, &qmv)).unwrap();
+            }
+            None
+        }
+        _ => None,
+    };
+}
+
+/// Sends the ghost wall as a move if it's `side`'s turn and the wall can be
+/// placed, returning whether it was.
+fn place_ghost(game: &Quoridor, state: &mut State, side: PlayerID, agent: &QAgent) -> bool {
+    let ghost = match state.ghost {
+        Some(ghost) => ghost,
+        None => return false,
+    };
+    let qmv = Move::PlaceWall(ghost);
+    if game.turn_of() == side && Board::from_game(game).is_legal(&qmv) {
+        agent.send_move(RulebookMove::wrap(game, &qmv)).unwrap();
+        state.ghost = None;
+        true
+    } else {
+        false
+    }
+}
+
+/// Moves `pos` by `(dx, dy)`, keeping both coordinates within `min..=max`.
+fn step(pos: Position, (dx, dy): (i8, i8), min: u8, max: u8) -> Position {
+    let clamp = |v: u8, d: i8| i8::min(i8::max(v as i8 + d, min as i8), max as i8) as u8;
+    Position::from((clamp(pos.x, dx), clamp(pos.y, dy)))
+}
+
+/// Handles a key press on the board, returning whether it was one of ours.
+///
+/// The arrow keys move a cursor over the squares, or the ghost wall while one is
+/// shown; Enter or Space selects and moves pawns or places the wall, `w` shows
+/// or hides a ghost wall at the cursor, `r` rotates it, `d` reads out the
+/// position and Escape clears any selection.
+fn on_key(
+    game: &Quoridor,
+    state: &mut State,
+    side: PlayerID,
+    agent: &QAgent,
+    layout: &Layout,
+    key: &str,
+) -> bool {
+    // Up is away from the player, which is down the rows for player 1.
+    let up = if layout.flipped() { -1 } else { 1 };
+    let delta = match key {
+        "ArrowUp" => Some((0, up)),
+        "ArrowDown" => Some((0, -up)),
+        "ArrowLeft" => Some((-1, 0)),
+        "ArrowRight" => Some((1, 0)),
+        _ => None,
+    };
+
+    let cursor = state.cursor.unwrap_or_else(|| {
+        let own = game
+            .pawns()
+            .iter()
+            .find(|(id, _)| id.owned_by(game) == side)
+            .map(|(_, &pos)| pos);
+        own.unwrap_or_else(|| Position::from((0, 0)))
+    });
+    state.cursor = Some(cursor);
+
+    if let Some(delta) = delta {
+        match state.ghost {
+            Some(ghost) => {
+                let ghost = Wall {
+                    position: step(ghost.position, delta, 1, layout.size - 1),
+                    ..ghost
+                };
+                state.ghost = Some(ghost);
+                announce_ghost(game, &ghost);
+            }
+            None => {
+                let pos = step(cursor, delta, 0, layout.size - 1);
+                state.cursor = Some(pos);
+                a11y::announce(&a11y::describe_square(game, pos));
+            }
+        }
+        return true;
+    }
+
+    match key {
+        "Enter" | " " => {
+            if state.ghost.is_some() {
+                if !place_ghost(game, state, side, agent) {
+                    a11y::announce("That wall can't be placed now");
+                }
+            } else {
+                select_square(game, state, side, agent, cursor);
+                if let Some(pos) = state.highlight {
+                    a11y::announce(&format!("Selected pawn on {}", format_square(pos)));
+                }
+            }
+        }
+        "w" | "W" => {
+            state.ghost = match state.ghost {
+                Some(_) => None,
+                None => {
+                    let ghost = Wall {
+                        position: step(cursor, (1, 1), 1, layout.size - 1),
+                        orientation: Orientation::Horizontal,
+                        wall_type: WallType::Simple,
+                    };
+                    announce_ghost(game, &ghost);
+                    Some(ghost)
+                }
+            };
+        }
+        "r" | "R" => {
+            if let Some(ghost) = state.ghost {
+                let ghost = Wall {
+                    orientation: rotate(ghost.orientation),
+                    ..ghost
+                };
+                state.ghost = Some(ghost);
+                announce_ghost(game, &ghost);
+            }
+        }
+        "d" | "D" => a11y::announce(&a11y::describe(game, side)),
+        "Escape" => {
+            state.ghost = None;
+            state.highlight = None;
+        }
+        _ => return false,
+    }
+    true
+}
+
+fn announce_ghost(game: &Quoridor, ghost: &Wall) {
+    let legal = Board::from_game(game).is_legal(&Move::PlaceWall(*ghost));
+    a11y::announce(&format!(
+        "Wall {}{}",
+        format_wall(ghost),
+        if legal { "" } else { ", can't be placed" }
+    ));
+}
+
+/// Handles a touch on the wall slots: the first tap shows a ghost wall, tapping
+/// its centre turns it, dragging moves it and tapping the rest of it places it.
+/// Returns false when the touch wasn't on a wall, for it to be handled as a
+/// pawn move instead.
+fn on_touch_down(
+    game: &Quoridor,
+    state: &mut State,
+    side: PlayerID,
+    agent: &QAgent,
+    layout: &Layout,
+    x: f64,
+    y: f64,
+) -> bool {
+    if let Some(ghost) = state.ghost {
+        if layout.wall_contains(&ghost, x, y) {
+            let (cx, cy) = layout.wall_centre(&ghost);
+            if (x - cx).abs() < layout.wall_width && (y - cy).abs() < layout.wall_width {
+                state.ghost = Some(Wall {
+                    orientation: rotate(ghost.orientation),
+                    ..ghost
+                });
+                state.dragging = true;
+            } else {
+                place_ghost(game, state, side, agent);
+            }
+            return true;
+        }
+    }
+
+    match layout.touch_target(x, y) {
+        Some((position, orientation)) => {
+            let orientation = orientation
+                .or_else(|| state.ghost.map(|g| g.orientation))
+                .unwrap_or(Orientation::Horizontal);
+            state.ghost = Some(Wall {
+                position,
+                orientation,
+                wall_type: WallType::Simple,
+            });
+            state.highlight = None;
+            state.dragging = true;
+            true
+        }
+        None => {
+            state.ghost = None;
+            false
+        }
+    }
 }
 
 fn render_game(
@@ -440,72 +1024,150 @@ fn render_game(
     data_div: &web_sys::HtmlElement,
     game: &Quoridor,
     state: &State,
+    layout: &Layout,
 ) {
+    render_board(context, &Board::from_game(game), state, layout);
+    status::render(data_div, game, layout.side, &get_colors().pawns);
+}
+
+/// Draws a position, which needn't come from a game, see the board editor.
+fn render_board(
+    context: &web_sys::CanvasRenderingContext2d,
+    board: &Board,
+    state: &State,
+    layout: &Layout,
+) {
+    // The backing store changes with resizes, so scale from board units to it afresh.
+    let pixels = context
+        .canvas()
+        .map_or(STANDARD_CANVAS_SIZE, |canvas| canvas.width() as f64);
+    let scale = pixels / STANDARD_CANVAS_SIZE;
+    if layout.flipped() {
+        context.set_transform(scale, 0.0, 0.0, -scale, 0.0, pixels).unwrap();
+    } else {
+        context.set_transform(scale, 0.0, 0.0, scale, 0.0, 0.0).unwrap();
+    }
+
     context.set_fill_style(&get_colors().base);
     context.fill_rect(0.0, 0.0, STANDARD_CANVAS_SIZE, STANDARD_CANVAS_SIZE);
 
     let colors = &get_colors();
 
     context.set_fill_style(&colors.wall_slot);
-    for i in 0..10 {
+    for i in 0..=layout.size {
         context.fill_rect(
-            i as f64 * (WALL_WIDTH * (1.0 + WALL_TO_SPOT_RATIO)),
+            i as f64 * layout.unit_width,
             0.0,
-            WALL_WIDTH,
+            layout.wall_width,
             STANDARD_CANVAS_SIZE,
         );
         context.fill_rect(
             0.0,
-            i as f64 * (WALL_WIDTH * (1.0 + WALL_TO_SPOT_RATIO)),
+            i as f64 * layout.unit_width,
             STANDARD_CANVAS_SIZE,
-            WALL_WIDTH,
+            layout.wall_width,
         );
     }
 
-    context.set_fill_style(&colors.wall);
-    for wall in game.walls().iter() {
-        let h_control = (wall.orientation == Orientation::Horizontal) as u8 as f64;
-        let v_conrol = (wall.orientation == Orientation::Vertical) as u8 as f64;
+    let progress = animation::progress(state.animation_start);
+    let is_last = |wall: &Wall| match state.last_move {
+        Some(Move::PlaceWall(last)) => {
+            last.position == wall.position && last.orientation == wall.orientation
+        }
+        _ => false,
+    };
+
+    if let Some(Move::MovePawn(from, _)) = state.last_move {
+        let (x, y) = (from.x as f64, (layout.size - 1 - from.y) as f64);
+        context.set_global_alpha(0.25);
+        context.set_fill_style(&colors.select);
+        context.fill_rect(
+            layout.wall_width + x * layout.unit_width,
+            layout.wall_width + y * layout.unit_width,
+            layout.spot_width,
+            layout.spot_width,
+        );
+        context.set_global_alpha(1.0);
+    }
 
+    context.set_fill_style(&colors.wall);
+    for wall in board.walls.iter() {
         match wall.wall_type {
             WallType::Simple => {
-                let x = wall.position.x as f64 * UNIT_WIDTH - h_control * SPOT_WIDTH;
-                let y = (9 - wall.position.y) as f64 * UNIT_WIDTH - v_conrol * SPOT_WIDTH;
-                context.fill_rect(
-                    x,
-                    y,
-                    WALL_WIDTH * v_conrol + (UNIT_WIDTH + SPOT_WIDTH) * h_control,
-                    WALL_WIDTH * h_control + (UNIT_WIDTH + SPOT_WIDTH) * v_conrol,
-                );
+                let (x, y, width, height) = layout.wall_rect(wall);
+                if is_last(wall) && progress < 1.0 {
+                    // Slides in across its slot while fading in.
+                    let offset = (1.0 - progress) * layout.unit_width;
+                    let (dx, dy) = match wall.orientation {
+                        Orientation::Horizontal => (0.0, -offset),
+                        Orientation::Vertical => (-offset, 0.0),
+                    };
+                    context.set_global_alpha(progress);
+                    context.fill_rect(x + dx, y + dy, width, height);
+                    context.set_global_alpha(1.0);
+                } else {
+                    context.fill_rect(x, y, width, height);
+                }
+                if is_last(wall) {
+                    context.set_stroke_style(&colors.select);
+                    context.set_line_width(layout.wall_width / 4.0);
+                    context.stroke_rect(x, y, width, height);
+                }
             }
             WallType::Single => unimplemented!("Can't render single walls! ( Yet ;) )"),
             WallType::Strong => unimplemented!("Can't strong single walls! ( Yet ;) )"),
         }
     }
 
-    for (&id, &pos) in game.pawns().iter() {
-        let (x, y) = (pos.x as f64, (8 - pos.y) as f64);
+    if let (Some(cursor), None) = (state.cursor, state.ghost) {
+        let (x, y) = (cursor.x as f64, (layout.size - 1 - cursor.y) as f64);
+        context.set_stroke_style(&colors.select);
+        context.set_line_width(layout.wall_width / 2.0);
+        context.stroke_rect(
+            layout.wall_width + x * layout.unit_width,
+            layout.wall_width + y * layout.unit_width,
+            layout.spot_width,
+            layout.spot_width,
+        );
+    }
+
+    if let Some(ghost) = state.ghost {
+        // Faded further when it couldn't be placed.
+        let legal = board.is_legal(&Move::PlaceWall(ghost));
+        context.set_global_alpha(if legal { 0.7 } else { 0.3 });
+        context.set_fill_style(&colors.select);
+        let (x, y, width, height) = layout.wall_rect(&ghost);
+        context.fill_rect(x, y, width, height);
+        context.set_global_alpha(1.0);
+    }
+
+    // Pawns are in order of their ids, see `Board::from_game`.
+    for (id, &pos) in board.pawns.iter().enumerate() {
+        let (mut x, mut y) = (pos.x as f64, (layout.size - 1 - pos.y) as f64);
+        if let Some(Move::MovePawn(from, to)) = state.last_move {
+            if to == pos {
+                // Glides from where it came from.
+                let (from_x, from_y) = (from.x as f64, (layout.size - 1 - from.y) as f64);
+                x = from_x + (x - from_x) * progress;
+                y = from_y + (y - from_y) * progress;
+            }
+        }
 
         let color = match state.highlight {
             Some(hpos) if hpos == pos => &colors.select,
-            _ => &colors.pawns[id as usize],
+            _ => &colors.pawns[id],
         };
 
         context.set_fill_style(color);
 
-        context.fill_rect(
-            WALL_WIDTH + x * UNIT_WIDTH,
-            WALL_WIDTH + y * UNIT_WIDTH,
-            SPOT_WIDTH,
-            SPOT_WIDTH,
+        theme::draw_pawn(
+            context,
+            colors.shapes[id],
+            layout.wall_width + x * layout.unit_width,
+            layout.wall_width + y * layout.unit_width,
+            layout.spot_width,
         );
     }
-
-    data_div.set_inner_html(
-        &game
-            .wall_counts()
-            .fold(String::from("Walls left ->"), |f, i| f + &format!(" Player {},", i) + &i.to_string()),
-    );
 }
 
 trait PID {
@@ -538,6 +1200,11 @@ impl<G: Game> WSAgent<G> for WebSocket {
                 let event = bincode::deserialize(&array.to_vec()).unwrap();
                 console_log!("sent the shit");
                 etx.send(event).unwrap();
+            } else if let Some(text) = e.data().as_string() {
+                // Text frames are control messages, see `common::control`.
+                if let Ok(control) = serde_json::from_str(&text) {
+                    status::on_control(&control);
+                }
             }
         }) as Box<dyn FnMut(MessageEvent)>);
 
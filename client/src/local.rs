@@ -0,0 +1,420 @@
+//! Games run in the browser rather than on the server: hot-seat games, two
+//! players sharing one browser, games against the AI in `ai`, puzzles and
+//! correspondence games, whose moves go by the server. The game runs here, and
+//! the agent in play passes between sides as they move.
+
+use crate::{
+    a11y, ai,
+    correspondence::Link,
+    puzzle::{Solving, Verdict},
+    status, QAgent, QGameEvent, QGameType,
+};
+use common::{
+    ai::Rng,
+    board::{Board, SizedGame},
+    correspondence::GameView,
+    notation::{parse_board_move, parse_move},
+    position::setup_moves,
+    puzzle::Puzzle,
+};
+use quoridor_core::{rulebooks::*, *};
+use std::error::Error;
+use tbmp_core::*;
+
+type GameFn = Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>;
+
+impl SizedGame for QGameType {
+    type Game = (Vec<QAgent>, GameFn);
+
+    fn standard_game(&self) -> Self::Game {
+        self.new_game()
+    }
+}
+
+/// Which way up the board is drawn in a hot-seat game.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    /// As the side to move sees it, turning after every move.
+    Turn,
+    /// As player 0 sees it.
+    Fixed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    HotSeat(View),
+    /// Against the AI, which searches `depth` plies and plays the other side.
+    Ai {
+        side: PlayerID,
+        depth: u8,
+    },
+    /// Solving a puzzle as `side`, the replies coming from its solution.
+    Puzzle {
+        side: PlayerID,
+    },
+    /// Playing `side` of a correspondence game, the replies coming from the server.
+    Correspondence {
+        side: PlayerID,
+    },
+}
+
+pub struct Options {
+    pub game_type: QGameType,
+    pub size: u8,
+    pub mode: Mode,
+    /// A position to start from instead, see `common::position`.
+    pub position: Option<String>,
+}
+
+/// The value of `key` in a page query like `?mode=hotseat&size=7`, still encoded.
+pub fn query_param<'a>(search: &'a str, key: &str) -> Option<&'a str> {
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter_map(|pair| {
+            let mut kv = pair.splitn(2, '=');
+            Some((kv.next()?, kv.next()?))
+        })
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+/// Decodes a value from a page query.
+pub fn decode(value: &str) -> Option<String> {
+    js_sys::decode_uri_component(&value.replace('+', " "))
+        .ok()
+        .map(String::from)
+}
+
+/// The id of the puzzle a page query like `?mode=puzzle&id=race-1` asks for.
+pub fn puzzle_from_query(search: &str) -> Option<String> {
+    if query_param(search, "mode")? != "puzzle" {
+        return None;
+    }
+    query_param(search, "id").map(String::from)
+}
+
+impl Options {
+    /// Reads a page query like `?mode=hotseat&size=7&view=fixed` or
+    /// `?mode=ai&side=second&level=hard`, `None` unless it asks for a local game.
+    /// Either can start from a `position` instead of the usual start.
+    pub fn from_query(game_type: &str, search: &str) -> Option<Options> {
+        let param = |key: &str| query_param(search, key);
+        let mode = match param("mode")? {
+            "hotseat" => Mode::HotSeat(match param("view") {
+                Some("fixed") => View::Fixed,
+                _ => View::Turn,
+            }),
+            "ai" => {
+                // The side asked for is the player's, the AI takes the other.
+                let side = match param("side") {
+                    Some("first") => 1,
+                    Some("second") => 0,
+                    _ => Rng::new(js_sys::Date::now() as u64).below(2) as PlayerID,
+                };
+                let depth = match param("level") {
+                    Some("easy") => 1,
+                    Some("hard") => 3,
+                    _ => 2,
+                };
+                Mode::Ai { side, depth }
+            }
+            _ => return None,
+        };
+        Some(Options {
+            game_type: match game_type {
+                "free" => QGameType::FreeQuoridor,
+                _ => QGameType::StandardQuoridor,
+            },
+            size: param("size")
+                .and_then(|s| s.parse().ok())
+                .filter(|s| common::board::BOARD_SIZES.contains(s))
+                .unwrap_or(common::board::BOARD_SIZE),
+            mode,
+            position: param("position").and_then(decode),
+        })
+    }
+}
+
+pub struct LocalGame {
+    /// The agents of the sides not in play, by side.
+    parked: Vec<Option<QAgent>>,
+    step: GameFn,
+    pub mode: Mode,
+    /// Set up for games against the AI.
+    worker: Option<ai::Worker>,
+    solving: Option<Solving>,
+    link: Option<Link>,
+}
+
+fn pending(agent: &QAgent) -> bool {
+    match agent {
+        QAgent::StandardQuoridor(core) => !core.move_channel.is_empty(),
+        QAgent::FreeQuoridor(core) => !core.move_channel.is_empty(),
+    }
+}
+
+fn drain(agent: &QAgent) {
+    while agent.recv_event().is_ok() {}
+}
+
+/// The starting position of a standard game on a board of `size`.
+pub fn start_position(size: u8) -> Option<Board> {
+    let (_, _, game) = LocalGame::create(&QGameType::StandardQuoridor, size)?;
+    Some(Board::from_game(&game))
+}
+
+impl LocalGame {
+    /// Creates a game, its agents' `GameStart` already taken.
+    fn create(game_type: &QGameType, size: u8) -> Option<(Vec<Option<QAgent>>, GameFn, Quoridor)> {
+        let (agents, step) = game_type.new_game_with_size(size)?;
+        let mut game = None;
+        for agent in &agents {
+            while let Ok(event) = agent.recv_event() {
+                if let QGameEvent::GameStart(g, _) = event {
+                    game = Some(g);
+                }
+            }
+        }
+        Some((agents.into_iter().map(Some).collect(), step, game?))
+    }
+
+    /// Plays `moves` out on a game just created, to set a position up.
+    fn set_up(
+        parked: &[Option<QAgent>],
+        step: &mut GameFn,
+        game: &mut Quoridor,
+        moves: &[Move],
+    ) -> Option<()> {
+        for qmv in moves {
+            let agent = parked[game.turn_of() as usize].as_ref()?;
+            agent.send_move(RulebookMove::wrap(game, qmv)).ok()?;
+            step().ok()?;
+            parked.iter().flatten().for_each(drain);
+            game.apply_move(qmv);
+        }
+        Some(())
+    }
+
+    /// Starts a game, returning it along with the agent put in play: the side
+    /// to move first in hot-seat games, the player's side against the AI.
+    pub fn start(options: &Options) -> Result<(LocalGame, QAgent, Quoridor), String> {
+        let failed = || String::from("The game couldn't be set up.");
+        let target = match &options.position {
+            Some(position) => Some(position.parse::<Board>().map_err(|e| e.to_string())?),
+            None => None,
+        };
+        let size = target.as_ref().map_or(options.size, |t| t.size);
+        let (mut parked, mut step, mut game) =
+            Self::create(&options.game_type, size).ok_or_else(failed)?;
+        if let Some(target) = &target {
+            let moves = setup_moves(&Board::from_game(&game), target)
+                .ok_or_else(|| String::from("That position can't come up in a game."))?;
+            Self::set_up(&parked, &mut step, &mut game, &moves).ok_or_else(failed)?;
+        }
+
+        let (in_play, worker) = match options.mode {
+            Mode::HotSeat(_) => (game.turn_of(), None),
+            Mode::Ai { side, .. } => {
+                let mut names = vec![None; game.get_player_count() as usize];
+                names[side as usize] = Some(String::from("Computer"));
+                status::set_nicknames(names);
+                let worker = ai::Worker::new()
+                    .ok_or_else(|| String::from("The computer player couldn't be started."))?;
+                ((side + 1) % game.get_player_count(), Some(worker))
+            }
+            Mode::Puzzle { .. } | Mode::Correspondence { .. } => return Err(failed()),
+        };
+        let agent = parked[in_play as usize].take().ok_or_else(failed)?;
+        let local = LocalGame {
+            parked,
+            step,
+            mode: options.mode,
+            worker,
+            solving: None,
+            link: None,
+        };
+        Ok((local, agent, game))
+    }
+
+    /// Sets a puzzle's position up, putting the solver's agent in play.
+    pub fn start_puzzle(puzzle: &Puzzle) -> Option<(LocalGame, QAgent, Quoridor)> {
+        let (mut parked, mut step, mut game) =
+            Self::create(&QGameType::StandardQuoridor, puzzle.size)?;
+        let mut board = Board::from_game(&game);
+        let mut moves = vec![];
+        for notation in &puzzle.moves {
+            let qmv = parse_board_move(notation, &board).ok()?;
+            board.apply(&qmv);
+            moves.push(qmv);
+        }
+        Self::set_up(&parked, &mut step, &mut game, &moves)?;
+
+        let side = game.turn_of();
+        let agent = parked[side as usize].take()?;
+        let local = LocalGame {
+            parked,
+            step,
+            mode: Mode::Puzzle { side },
+            worker: None,
+            solving: Some(Solving::new(puzzle, side)),
+            link: None,
+        };
+        Some((local, agent, game))
+    }
+
+    /// Plays a correspondence game's moves out, putting the agent of the seat
+    /// `token` holds in play.
+    pub fn start_correspondence(
+        view: &GameView,
+        token: String,
+    ) -> Result<(LocalGame, QAgent, Quoridor), String> {
+        let failed = || String::from("The game couldn't be set up.");
+        let link = Link::new(view, token)
+            .ok_or_else(|| String::from("This link isn't for a seat in the game."))?;
+        let (mut parked, mut step, mut game) =
+            Self::create(&QGameType::StandardQuoridor, view.size).ok_or_else(failed)?;
+        let mut board = Board::from_game(&game);
+        let mut moves = vec![];
+        for notation in &view.moves {
+            let qmv = parse_board_move(notation, &board).map_err(|e| e.to_string())?;
+            board.apply(&qmv);
+            moves.push(qmv);
+        }
+        Self::set_up(&parked, &mut step, &mut game, &moves).ok_or_else(failed)?;
+        status::set_nicknames(view.players.clone());
+
+        let side = link.side();
+        let agent = parked[side as usize].take().ok_or_else(failed)?;
+        let local = LocalGame {
+            parked,
+            step,
+            mode: Mode::Correspondence { side },
+            worker: None,
+            solving: None,
+            link: Some(link),
+        };
+        Ok((local, agent, game))
+    }
+
+    pub fn hot_seat(&self) -> bool {
+        match self.mode {
+            Mode::HotSeat(_) => true,
+            Mode::Ai { .. } | Mode::Puzzle { .. } | Mode::Correspondence { .. } => false,
+        }
+    }
+
+    pub fn puzzle(&self) -> bool {
+        self.solving.is_some()
+    }
+
+    pub fn correspondence(&self) -> bool {
+        self.link.is_some()
+    }
+
+    /// The side playing at this screen while `to_move` is moving.
+    pub fn side(&self, to_move: PlayerID, game: &Quoridor) -> PlayerID {
+        match self.mode {
+            Mode::HotSeat(_) => to_move,
+            Mode::Ai { side, .. } => (side + 1) % game.get_player_count(),
+            Mode::Puzzle { side } | Mode::Correspondence { side } => side,
+        }
+    }
+
+    /// The side whose view of the board to draw while `to_move` is moving.
+    pub fn viewer(&self, to_move: PlayerID, game: &Quoridor) -> PlayerID {
+        match self.mode {
+            Mode::HotSeat(View::Fixed) => 0,
+            _ => self.side(to_move, game),
+        }
+    }
+
+    /// Plays the move `agent` sent, if it sent one, returning why it was refused.
+    ///
+    /// Every agent hears of the move, so only `agent`'s events are kept; the rest
+    /// would repeat them.
+    pub fn play(&mut self, agent: &QAgent) -> Result<(), String> {
+        if !pending(agent) {
+            return Ok(());
+        }
+        self.step_game()
+    }
+
+    fn step_game(&mut self) -> Result<(), String> {
+        let result = (self.step)().map(|_| ()).map_err(|e| e.to_string());
+        self.parked.iter().flatten().for_each(drain);
+        result
+    }
+
+    /// Lets the AI think when it's its move, and plays its move once it has one.
+    pub fn play_ai(&mut self, game: &Quoridor) -> Result<(), String> {
+        let (side, depth) = match self.mode {
+            Mode::Ai { side, depth } => (side, depth),
+            _ => return Ok(()),
+        };
+        let worker = match self.worker.as_mut() {
+            Some(worker) => worker,
+            None => return Ok(()),
+        };
+        if game.turn_of() != side || Board::from_game(game).winner().is_some() {
+            return Ok(());
+        }
+        let reply = match worker.reply() {
+            Some(Some(reply)) => reply,
+            Some(None) => {
+                // Asking again would only get the same answer.
+                self.worker = None;
+                let reason = "It couldn't find a move to play. Reload the page to play again.";
+                status::show_result("The computer has no move", reason, false);
+                a11y::announce("The computer has no move.");
+                return Ok(());
+            }
+            None => {
+                worker.think(&Board::from_game(game), depth);
+                return Ok(());
+            }
+        };
+        self.play_parked(game, &reply)
+    }
+
+    /// Plays the opponent's reply once the solver has moved.
+    pub fn play_puzzle(&mut self, game: &Quoridor) -> Result<(), String> {
+        match self.solving.as_ref().and_then(|s| s.reply(game)) {
+            Some(reply) => self.play_parked(game, &reply),
+            None => Ok(()),
+        }
+    }
+
+    /// Plays the opponent's move in a correspondence game once the server has it.
+    pub fn play_correspondence(&mut self, game: &Quoridor) -> Result<(), String> {
+        match self.link.as_mut().and_then(|l| l.next_move()) {
+            Some(reply) => self.play_parked(game, &reply),
+            None => Ok(()),
+        }
+    }
+
+    /// Plays a move for the side to move, whose agent is parked.
+    fn play_parked(&mut self, game: &Quoridor, notation: &str) -> Result<(), String> {
+        let qmv = parse_move(notation, game).map_err(|e| e.to_string())?;
+        if let Some(agent) = &self.parked[game.turn_of() as usize] {
+            agent.send_move(RulebookMove::wrap(game, &qmv)).unwrap();
+        }
+        self.step_game()
+    }
+
+    /// Follows a move about to be played on `game`, judging it in puzzles and
+    /// passing it on in correspondence games.
+    pub fn observe(&mut self, game: &Quoridor, qmv: &Move) -> Option<Verdict> {
+        if let Some(link) = self.link.as_mut() {
+            link.observe(qmv);
+        }
+        self.solving.as_mut()?.observe(game, qmv)
+    }
+
+    /// Passes the agent in play from `from` to `to`, once `agent` has no events left.
+    pub fn hand_over(&mut self, agent: &mut QAgent, from: PlayerID, to: PlayerID) {
+        if let Some(next) = self.parked[to as usize].take() {
+            self.parked[from as usize] = Some(std::mem::replace(agent, next));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Puzzle mode: the player looks for the solution of a puzzle from the server,
+//! and the opponent answers from it.
+
+use crate::{a11y, status};
+use common::{
+    notation::{format_move, parse_move},
+    puzzle::Puzzle,
+};
+use quoridor_core::*;
+use tbmp_core::*;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Fetches a puzzle from the server, handing it to `then`.
+pub fn fetch(id: &str, then: impl FnOnce(Puzzle) + 'static) -> Option<()> {
+    let request = web_sys::XmlHttpRequest::new().ok()?;
+    let url = format!("/puzzle/{}", String::from(js_sys::encode_uri_component(id)));
+    request.open("GET", &url).ok()?;
+    let response = request.clone();
+    let on_load = Closure::once(move || {
+        let puzzle = Some(&response)
+            .filter(|r| r.status().ok() == Some(200))
+            .and_then(|r| r.response_text().ok()?)
+            .and_then(|text| serde_json::from_str(&text).ok());
+        match puzzle {
+            Some(puzzle) => then(puzzle),
+            None => status::show_result("Puzzle not found", "There's no such puzzle.", false),
+        }
+    });
+    request.set_onload(Some(on_load.as_ref().unchecked_ref()));
+    on_load.forget();
+    request.send().ok()
+}
+
+pub enum Verdict {
+    Solved,
+    /// The solver left the solution, which went on with this move.
+    Wrong(String),
+}
+
+impl Verdict {
+    pub fn show(&self) {
+        let (title, reason) = match self {
+            Verdict::Solved => (
+                String::from("Solved!"),
+                String::from("That's the solution."),
+            ),
+            Verdict::Wrong(expected) => (
+                String::from("Not the solution"),
+                format!(
+                    "The solution goes on with {}. Reload the page to try again.",
+                    expected
+                ),
+            ),
+        };
+        status::show_result(&title, &reason, false);
+        a11y::announce(&format!("{} {}", title, reason));
+    }
+}
+
+/// How far the solver got into a puzzle.
+pub struct Solving {
+    side: PlayerID,
+    /// The solutions the moves so far agree with.
+    lines: Vec<Vec<String>>,
+    /// Moves played since the puzzle's position.
+    ply: usize,
+    done: bool,
+}
+
+impl Solving {
+    pub fn new(puzzle: &Puzzle, side: PlayerID) -> Self {
+        Solving {
+            side,
+            lines: puzzle.solutions.clone(),
+            ply: 0,
+            done: false,
+        }
+    }
+
+    /// Follows a move about to be played on `game`, judging the solver's moves.
+    pub fn observe(&mut self, game: &Quoridor, qmv: &Move) -> Option<Verdict> {
+        if self.done || self.lines.is_empty() {
+            return None;
+        }
+        let ply = self.ply;
+        self.ply += 1;
+        // Compared in short notation, so either form of a move matches.
+        let played = format_move(qmv);
+        let expected = self.lines[0][ply].clone();
+        self.lines.retain(|line| {
+            line.get(ply)
+                .and_then(|n| parse_move(n, game).ok())
+                .map_or(false, |m| format_move(&m) == played)
+        });
+        if game.turn_of() != self.side {
+            return None;
+        }
+        if self.lines.is_empty() {
+            self.done = true;
+            Some(Verdict::Wrong(expected))
+        } else if self.lines.iter().any(|line| line.len() == self.ply) {
+            self.done = true;
+            Some(Verdict::Solved)
+        } else {
+            None
+        }
+    }
+
+    /// The opponent's reply, once the solver has found the right move.
+    pub fn reply(&self, game: &Quoridor) -> Option<String> {
+        if self.done || game.turn_of() == self.side {
+            return None;
+        }
+        self.lines.first()?.get(self.ply).cloned()
+    }
+}
@@ -0,0 +1,317 @@
+use crate::a11y;
+use common::{
+    control::{ClientControl, ServerControl},
+    pawn_owner,
+    record::Termination,
+};
+use quoridor_core::*;
+use std::cell::RefCell;
+use tbmp_core::*;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::WebSocket;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connection {
+    Connecting,
+    /// Connected, but the game hasn't started.
+    Waiting,
+    Playing,
+    Disconnected,
+    /// A hot-seat game, played in this browser.
+    Local,
+    /// A game against the AI, played in this browser.
+    Computer,
+    /// Solving a puzzle, in this browser.
+    Puzzle,
+    /// Setting a position up in the board editor.
+    Editor,
+    /// A correspondence game, kept by the server between visits.
+    Correspondence,
+}
+
+impl Connection {
+    fn describe(self) -> &'static str {
+        match self {
+            Connection::Connecting => "Connecting…",
+            Connection::Waiting => "Waiting for an opponent",
+            Connection::Playing => "Connected",
+            Connection::Disconnected => "Disconnected",
+            Connection::Local => "Hot-seat game",
+            Connection::Computer => "Playing the computer",
+            Connection::Puzzle => "Puzzle",
+            Connection::Editor => "Board editor",
+            Connection::Correspondence => "Correspondence game",
+        }
+    }
+
+    fn class(self) -> &'static str {
+        match self {
+            Connection::Connecting | Connection::Waiting => "pending",
+            Connection::Playing
+            | Connection::Local
+            | Connection::Computer
+            | Connection::Puzzle
+            | Connection::Editor
+            | Connection::Correspondence => "online",
+            Connection::Disconnected => "offline",
+        }
+    }
+}
+
+thread_local! {
+    static CONNECTION: RefCell<Connection> = RefCell::new(Connection::Connecting);
+    /// Nicknames by side, as the server last sent them or a local game set them.
+    static NICKNAMES: RefCell<Vec<Option<String>>> = RefCell::new(vec![]);
+    /// The side this connection plays, as the server last seated it.
+    static SEAT: RefCell<PlayerID> = RefCell::new(0);
+}
+
+fn nickname(player: PlayerID) -> Option<String> {
+    NICKNAMES.with(|names| names.borrow().get(player as usize).cloned().flatten())
+}
+
+pub fn set_nicknames(names: Vec<Option<String>>) {
+    NICKNAMES.with(|n| *n.borrow_mut() = names);
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn element(id: &str) -> Option<web_sys::Element> {
+    web_sys::window()?.document()?.get_element_by_id(id)
+}
+
+pub fn connection() -> Connection {
+    CONNECTION.with(|c| *c.borrow())
+}
+
+pub fn set_connection(connection: Connection) {
+    CONNECTION.with(|c| *c.borrow_mut() = connection);
+    if let Some(e) = element("connection") {
+        e.set_class_name(connection.class());
+        e.set_text_content(Some(connection.describe()));
+    }
+}
+
+/// Says which side this connection was seated on, before the game starts.
+pub fn set_seat(side: PlayerID) {
+    SEAT.with(|s| *s.borrow_mut() = side);
+    if let Some(e) = element("seat") {
+        let text = match side {
+            0 => String::from("You move first"),
+            1 => String::from("You move second"),
+            _ => format!("You are player {}", side),
+        };
+        e.set_text_content(Some(&text));
+    }
+}
+
+/// Says until when the player to move has to move, in correspondence games.
+pub fn set_deadline(text: &str) {
+    if let Some(e) = element("deadline") {
+        e.set_text_content(Some(text));
+    }
+}
+
+/// The player's nickname, falling back to their number, marked if it's us.
+pub fn player_name(player: PlayerID, side: PlayerID) -> String {
+    match (nickname(player), player == side) {
+        (Some(name), true) => format!("{} (you)", name),
+        (None, true) => String::from("You"),
+        (Some(name), false) => name,
+        (None, false) => format!("Player {}", player),
+    }
+}
+
+/// Fills the status panel: a chip per player with their colour, name and walls
+/// left, the one whose turn it is marked.
+pub fn render(panel: &web_sys::HtmlElement, game: &Quoridor, side: PlayerID, pawns: &[JsValue]) {
+    let colour = |player: PlayerID| {
+        game.pawns()
+            .iter()
+            .map(|(&id, _)| id)
+            .filter(|&id| pawn_owner(game, id) == player)
+            .min()
+            .and_then(|id| pawns.get(id as usize)?.as_string())
+            .unwrap_or_default()
+    };
+
+    let chips: String = game
+        .wall_counts()
+        .enumerate()
+        .map(|(player, walls)| {
+            let player = player as PlayerID;
+            let turn = game.turn_of() == player;
+            format!(
+                r#"<span class="player{}"><span class="swatch" style="background: {}"></span>{}{} <span class="walls" title="{} walls left">{}</span></span>"#,
+                if turn { " turn" } else { "" },
+                colour(player),
+                if turn { "▶ " } else { "" },
+                escape_html(&player_name(player, side)),
+                walls,
+                r#"<span class="wall-token"></span>"#.repeat(walls as usize),
+            )
+        })
+        .collect();
+
+    if let Some(players) = panel.query_selector(".players").ok().flatten() {
+        players.set_inner_html(&chips);
+    }
+}
+
+/// Shows the end of game overlay, offering a rematch if `rematch`.
+pub fn show_result(title: &str, reason: &str, rematch: bool) {
+    if let (Some(overlay), Some(t), Some(r)) = (
+        element("overlay"),
+        element("result-title"),
+        element("result-reason"),
+    ) {
+        t.set_text_content(Some(title));
+        r.set_text_content(Some(reason));
+        set_rematch_note("");
+        set_rematch_button(if rematch { Some("Rematch") } else { None });
+        let _ = overlay.remove_attribute("hidden");
+    }
+}
+
+/// Tells the player the server can't be reached, offering a game against the
+/// AI instead, which `play` starts.
+pub fn offer_offline(play: impl FnOnce() + 'static) {
+    show_result(
+        "Can't reach the server",
+        "The game couldn't start, but you can still practice against the computer.",
+        false,
+    );
+    let button = match element("offline") {
+        Some(button) => button,
+        None => return,
+    };
+    let on_click = Closure::once(move || {
+        if let Some(button) = element("offline") {
+            let _ = button.set_attribute("hidden", "");
+        }
+        hide_result();
+        play();
+    });
+    let _ = button.remove_attribute("hidden");
+    if let Ok(button) = button.dyn_into::<web_sys::HtmlElement>() {
+        button.set_onclick(Some(on_click.as_ref().unchecked_ref()));
+    }
+    on_click.forget();
+}
+
+pub fn hide_result() {
+    if let Some(overlay) = element("overlay") {
+        let _ = overlay.set_attribute("hidden", "");
+    }
+}
+
+/// Relabels the rematch button, or hides it for `None`.
+fn set_rematch_button(label: Option<&str>) {
+    if let Some(button) = element("rematch") {
+        match label {
+            Some(label) => {
+                button.set_text_content(Some(label));
+                let _ = button.remove_attribute("hidden");
+                let _ = button.remove_attribute("disabled");
+            }
+            None => {
+                let _ = button.set_attribute("hidden", "");
+            }
+        }
+    }
+}
+
+fn set_rematch_note(note: &str) {
+    if let Some(e) = element("rematch-note") {
+        e.set_text_content(Some(note));
+    }
+}
+
+fn send_control(ws: &WebSocket, control: &ClientControl) {
+    let _ = ws.send_with_str(&serde_json::to_string(control).unwrap());
+}
+
+/// Makes the overlay's rematch button offer, or accept, a rematch over `ws`.
+pub fn init_rematch(ws: WebSocket) {
+    let button = match element("rematch") {
+        Some(button) => button,
+        None => return,
+    };
+    let on_click = Closure::wrap(Box::new(move || {
+        send_control(&ws, &ClientControl::RematchOffer);
+        if let Some(button) = element("rematch") {
+            let _ = button.set_attribute("disabled", "");
+        }
+        set_rematch_note("Waiting for your opponent…");
+    }) as Box<dyn FnMut()>);
+    if let Ok(button) = button.dyn_into::<web_sys::HtmlElement>() {
+        button.set_onclick(Some(on_click.as_ref().unchecked_ref()));
+    }
+    on_click.forget();
+}
+
+pub fn on_control(control: &ServerControl) {
+    match control {
+        ServerControl::Seated { side } => set_seat(*side),
+        // Shown from the next render on.
+        ServerControl::Players { names } => set_nicknames(names.clone()),
+        ServerControl::RematchOffered { .. } => {
+            set_rematch_note("Your opponent wants a rematch.");
+            if element("rematch").map_or(false, |b| !b.has_attribute("disabled")) {
+                set_rematch_button(Some("Accept rematch"));
+            }
+        }
+        ServerControl::RematchDeclined => {
+            set_rematch_note("There won't be a rematch.");
+            set_rematch_button(None);
+        }
+        ServerControl::RematchStarting { side } => {
+            set_rematch_note(&format!("Rematch starting, you are player {}.", side));
+            set_seat(*side);
+        }
+        ServerControl::MoveRefused { reason } => {
+            a11y::announce(&format!("Your move wasn't played: {}.", reason))
+        }
+        ServerControl::GameEnded {
+            winner,
+            termination,
+        } => {
+            let side = SEAT.with(|s| *s.borrow());
+            let (title, reason) = describe_result(*winner, termination, side);
+            // Whoever is still connected can play again.
+            show_result(&title, &reason, true);
+            a11y::announce(&format!("{} {}", title, reason));
+        }
+    }
+}
+
+/// The overlay's title and reason for a game that ended by `termination`.
+pub fn describe_result(
+    winner: Option<PlayerID>,
+    termination: &Termination,
+    side: PlayerID,
+) -> (String, String) {
+    let title = match winner {
+        Some(w) if w == side => "You won!",
+        Some(_) => "You lost",
+        None => "Draw",
+    };
+    let reason = match (termination, winner) {
+        (Termination::Goal, Some(w)) if w == side => "You reached the far side.".into(),
+        (Termination::Goal, Some(w)) => {
+            format!("{} reached the far side first.", player_name(w, side))
+        }
+        (Termination::Goal, None) => "Nobody can win from here.".into(),
+        (Termination::MoveLimit, _) => "The game went on too long to be decided.".into(),
+        (Termination::Forfeit, Some(w)) if w == side => "Your opponent forfeited the game.".into(),
+        (Termination::Forfeit, _) => "You forfeited the game.".into(),
+    };
+    (title.into(), reason)
+}
@@ -0,0 +1,250 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+const THEME_KEY: &str = "theme";
+const SHAPES_KEY: &str = "pawnShapes";
+
+pub struct Theme {
+    pub id: &'static str,
+    pub name: &'static str,
+    /// Page background and text, around the board.
+    pub page: &'static str,
+    pub text: &'static str,
+    pub base: &'static str,
+    pub wall_slot: &'static str,
+    pub wall: &'static str,
+    pub select: &'static str,
+    /// Pawn colours, taken in order, so the first two are the ones that tell
+    /// apart best.
+    pub pawns: &'static [&'static str],
+}
+
+pub const THEMES: &[Theme] = &[
+    Theme {
+        id: "classic",
+        name: "Classic",
+        page: "#222",
+        text: "#888",
+        base: "#50190A",
+        wall_slot: "#743c0d",
+        wall: "#996F38",
+        select: "#ACACAC",
+        pawns: &[
+            "hsl(0,100%,50%)",
+            "hsl(180,100%,50%)",
+            "hsl(90,100%,50%)",
+            "hsl(270,100%,50%)",
+        ],
+    },
+    Theme {
+        id: "light",
+        name: "Light",
+        page: "#f4f1ea",
+        text: "#444",
+        base: "#e8dcc4",
+        wall_slot: "#cbb894",
+        wall: "#6b4a2b",
+        select: "#7a7a7a",
+        pawns: &["#c0392b", "#2471a3", "#27ae60", "#8e44ad"],
+    },
+    Theme {
+        id: "dark",
+        name: "Dark",
+        page: "#111",
+        text: "#999",
+        base: "#2b2b2b",
+        wall_slot: "#3c3c3c",
+        wall: "#b0a080",
+        select: "#eeeeee",
+        pawns: &["#ff6b6b", "#5fb3f9", "#7bd88f", "#d49cff"],
+    },
+    Theme {
+        id: "high-contrast",
+        name: "High contrast",
+        page: "#000",
+        text: "#fff",
+        base: "#000000",
+        wall_slot: "#404040",
+        wall: "#ffffff",
+        select: "#00ffff",
+        pawns: &["#ffff00", "#ff00ff", "#00ff00", "#ff8000"],
+    },
+    // Both palettes are drawn from Okabe & Ito's colour-universal design set.
+    Theme {
+        id: "deuteranopia",
+        name: "Deuteranopia safe",
+        page: "#222",
+        text: "#aaa",
+        base: "#3a3a3a",
+        wall_slot: "#555555",
+        wall: "#f0f0f0",
+        select: "#999999",
+        pawns: &["#E69F00", "#56B4E9", "#CC79A7", "#F0E442"],
+    },
+    Theme {
+        id: "protanopia",
+        name: "Protanopia safe",
+        page: "#222",
+        text: "#aaa",
+        base: "#3a3a3a",
+        wall_slot: "#555555",
+        wall: "#f0f0f0",
+        select: "#999999",
+        pawns: &["#0072B2", "#F0E442", "#CC79A7", "#009E73"],
+    },
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Square,
+    Circle,
+    Triangle,
+    Diamond,
+}
+
+const SHAPES: [Shape; 4] = [Shape::Square, Shape::Circle, Shape::Triangle, Shape::Diamond];
+
+/// The settings chosen in the page, as stored in `localStorage`.
+pub struct Settings {
+    pub theme: &'static Theme,
+    pub shapes: bool,
+}
+
+impl Settings {
+    pub fn load() -> Settings {
+        let get = |key| storage().and_then(|s| s.get_item(key).ok()?);
+        Settings {
+            theme: get(THEME_KEY)
+                .and_then(|id| find(&id))
+                .unwrap_or(&THEMES[0]),
+            shapes: get(SHAPES_KEY).map_or(true, |v| v == "true"),
+        }
+    }
+
+    pub fn save(&self) {
+        if let Some(storage) = storage() {
+            let _ = storage.set_item(THEME_KEY, self.theme.id);
+            let _ = storage.set_item(SHAPES_KEY, &self.shapes.to_string());
+        }
+    }
+
+    /// The colour of each of `count` pawns.
+    pub fn pawn_colors(&self, count: u8) -> Vec<JsValue> {
+        let pawns = self.theme.pawns;
+        (0..count as usize)
+            .map(|i| JsValue::from_str(pawns[i % pawns.len()]))
+            .collect()
+    }
+
+    /// The shape of each of `count` pawns, all squares when shapes are off.
+    pub fn pawn_shapes(&self, count: u8) -> Vec<Shape> {
+        (0..count as usize)
+            .map(|i| {
+                if self.shapes {
+                    SHAPES[i % SHAPES.len()]
+                } else {
+                    Shape::Square
+                }
+            })
+            .collect()
+    }
+}
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+fn find(id: &str) -> Option<&'static Theme> {
+    THEMES.iter().find(|t| t.id == id)
+}
+
+fn control<T: JsCast>(document: &web_sys::Document, id: &str) -> Option<T> {
+    document.get_element_by_id(id)?.dyn_into::<T>().ok()
+}
+
+/// Fills in the page's settings controls and shows `settings`.
+pub fn init_controls(document: &web_sys::Document, settings: &Settings) -> Option<()> {
+    let theme: web_sys::HtmlSelectElement = control(document, "theme")?;
+    for t in THEMES {
+        let option = document.create_element("option").ok()?;
+        option.set_attribute("value", t.id).ok()?;
+        option.set_text_content(Some(t.name));
+        theme.append_child(&option).ok()?;
+    }
+    theme.set_value(settings.theme.id);
+
+    let shapes: web_sys::HtmlInputElement = control(document, "shapes")?;
+    shapes.set_checked(settings.shapes);
+
+    apply_page(document, settings.theme);
+    Some(())
+}
+
+/// Calls `f` with the new settings whenever a control changes, after saving them.
+pub fn on_change(mut f: impl FnMut(&Settings) + 'static) -> Option<()> {
+    let document = web_sys::window()?.document()?;
+    let theme: web_sys::HtmlSelectElement = control(&document, "theme")?;
+    let shapes: web_sys::HtmlInputElement = control(&document, "shapes")?;
+
+    let (theme_c, shapes_c) = (theme.clone(), shapes.clone());
+    let handler = Closure::wrap(Box::new(move || {
+        let settings = Settings {
+            theme: find(&theme_c.value()).unwrap_or(&THEMES[0]),
+            shapes: shapes_c.checked(),
+        };
+        settings.save();
+        apply_page(&document, settings.theme);
+        f(&settings);
+    }) as Box<dyn FnMut()>);
+    theme.set_onchange(Some(handler.as_ref().unchecked_ref()));
+    shapes.set_onchange(Some(handler.as_ref().unchecked_ref()));
+    handler.forget();
+    Some(())
+}
+
+fn apply_page(document: &web_sys::Document, theme: &Theme) {
+    if let Some(body) = document.body() {
+        let _ = body.style().set_property("background-color", theme.page);
+        let _ = body.style().set_property("color", theme.text);
+    }
+    if let Some(metadata) = control::<web_sys::HtmlElement>(document, "metadata") {
+        let _ = metadata.style().set_property("color", theme.text);
+    }
+}
+
+/// Fills a pawn of the given shape into the square at `(x, y)` with side `width`.
+pub fn draw_pawn(
+    context: &web_sys::CanvasRenderingContext2d,
+    shape: Shape,
+    x: f64,
+    y: f64,
+    width: f64,
+) {
+    let half = width / 2.0;
+    let (cx, cy) = (x + half, y + half);
+    match shape {
+        Shape::Square => context.fill_rect(x, y, width, width),
+        Shape::Circle => {
+            context.begin_path();
+            let _ = context.arc(cx, cy, half, 0.0, std::f64::consts::PI * 2.0);
+            context.fill();
+        }
+        Shape::Triangle => {
+            context.begin_path();
+            context.move_to(cx, y);
+            context.line_to(x + width, y + width);
+            context.line_to(x, y + width);
+            context.close_path();
+            context.fill();
+        }
+        Shape::Diamond => {
+            context.begin_path();
+            context.move_to(cx, y);
+            context.line_to(x + width, cy);
+            context.line_to(cx, y + width);
+            context.line_to(x, cy);
+            context.close_path();
+            context.fill();
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! A small alpha-beta engine and a random mover, both working on [`Board`].
+
+use crate::board::Board;
+use quoridor_core::*;
+use tbmp_core::*;
+
+const WIN_SCORE: i32 = 10_000;
+const PATH_WEIGHT: i32 = 10;
+const WALL_WEIGHT: i32 = 3;
+
+/// Xorshift generator, so the engines don't need an entropy source (or `rand`) on wasm.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+pub struct Ai {
+    pub depth: u8,
+    rng: Rng,
+}
+
+impl Ai {
+    pub fn new(depth: u8, seed: u64) -> Self {
+        Ai {
+            depth: depth.max(1),
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// The best move for the side to move, choosing randomly between equals.
+    pub fn best_move(&mut self, board: &Board) -> Option<Move> {
+        let mut best = vec![];
+        let mut best_score = -WIN_SCORE * 2;
+        for qmv in candidate_moves(board) {
+            let mut next = board.clone();
+            next.apply(&qmv);
+            let score = -negamax(&next, self.depth - 1, -WIN_SCORE * 2, -best_score + 1);
+            if score > best_score {
+                best_score = score;
+                best.clear();
+            }
+            if score == best_score {
+                best.push(qmv);
+            }
+        }
+        if best.is_empty() {
+            None
+        } else {
+            Some(best.swap_remove(self.rng.below(best.len())))
+        }
+    }
+}
+
+/// Static evaluation from the point of view of the side to move.
+pub fn evaluate(board: &Board) -> i32 {
+    let me = board.turn;
+    let opponent = (me + 1) % board.player_count();
+    match board.winner() {
+        Some(winner) if winner == me => return WIN_SCORE,
+        Some(_) => return -WIN_SCORE,
+        None => {}
+    }
+    let distance = |p: PlayerID| board.distance(p).map_or(WIN_SCORE, |d| d as i32);
+    let walls = |p: PlayerID| board.walls_left[p as usize] as i32;
+
+    PATH_WEIGHT * (distance(opponent) - distance(me)) + WALL_WEIGHT * (walls(me) - walls(opponent))
+}
+
+fn negamax(board: &Board, depth: u8, mut alpha: i32, beta: i32) -> i32 {
+    if depth == 0 || board.winner().is_some() {
+        return evaluate(board);
+    }
+    let mut best = -WIN_SCORE * 2;
+    for qmv in candidate_moves(board) {
+        let mut next = board.clone();
+        next.apply(&qmv);
+        let score = -negamax(&next, depth - 1, -beta, -alpha);
+        best = best.max(score);
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+/// Legal moves worth searching: every pawn move, and the walls touching the
+/// opponent's shortest path, since walls elsewhere rarely matter.
+pub fn candidate_moves(board: &Board) -> Vec<Move> {
+    let mut moves = board.pawn_move_list();
+    if board.walls_left[board.turn as usize] == 0 {
+        return moves;
+    }
+
+    let opponent = (board.turn + 1) % board.player_count();
+    let path = board.path(opponent).unwrap_or_default();
+    for square in path {
+        for &(dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            for &orientation in [Orientation::Horizontal, Orientation::Vertical].iter() {
+                let qmv = Move::PlaceWall(Wall {
+                    position: (square.x + dx, square.y + dy).into(),
+                    orientation,
+                    wall_type: WallType::Simple,
+                });
+                if !moves.contains(&qmv) && board.is_legal(&qmv) {
+                    moves.push(qmv);
+                }
+            }
+        }
+    }
+    moves
+}
+
+/// Picks uniformly between moving the pawn and placing a wall, then uniformly
+/// within that group, so random games still end in a reasonable time.
+pub fn random_move(board: &Board, rng: &mut Rng) -> Option<Move> {
+    let pawn_moves = board.pawn_move_list();
+    if board.walls_left[board.turn as usize] > 0 && rng.below(2) == 0 {
+        let walls: Vec<_> = board
+            .legal_moves()
+            .into_iter()
+            .filter(|qmv| matches!(qmv, Move::PlaceWall(_)))
+            .collect();
+        if !walls.is_empty() {
+            return Some(walls[rng.below(walls.len())]);
+        }
+    }
+    if pawn_moves.is_empty() {
+        None
+    } else {
+        Some(pawn_moves[rng.below(pawn_moves.len())])
+    }
+}
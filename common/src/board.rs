@@ -0,0 +1,285 @@
+//! A self-contained copy of the standard two player rules, for analysis.
+//!
+//! `Quoridor` only exposes what the rulebooks need to run a game, so engines and
+//! tools work on a [`Board`] snapshot instead, which can list legal moves and
+//! measure paths cheaply.
+
+use quoridor_core::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use tbmp_core::*;
+
+/// The size of a standard board, and of lobbies that don't ask for another.
+pub const BOARD_SIZE: u8 = 9;
+
+/// Board sizes lobbies can be created with. The geometry everywhere else
+/// follows the board a game reports, but quoridor_core only lays games out on a
+/// standard board, so that is the one size on offer until it can do others.
+pub const BOARD_SIZES: [u8; 1] = [BOARD_SIZE];
+
+/// The size of the board a game is played on.
+pub trait GameSize {
+    fn size(&self) -> u8;
+}
+
+impl GameSize for Quoridor {
+    fn size(&self) -> u8 {
+        BOARD_SIZE
+    }
+}
+
+/// Starting games on a board of a given size. Every crate generates its own
+/// rulebook types, so each implements `standard_game` and shares the size
+/// check here.
+pub trait SizedGame {
+    type Game;
+
+    /// A new game on a standard board.
+    fn standard_game(&self) -> Self::Game;
+
+    /// A new game on a board of `size`, `None` for a size quoridor_core can't
+    /// lay out. Only [`BOARD_SIZE`] can be, see [`BOARD_SIZES`].
+    fn new_game_with_size(&self, size: u8) -> Option<Self::Game> {
+        if BOARD_SIZES.contains(&size) {
+            Some(self.standard_game())
+        } else {
+            None
+        }
+    }
+}
+
+const DIRECTIONS: [(i8, i8); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Board {
+    pub size: u8,
+    /// Pawn positions, indexed by the owning player.
+    pub pawns: Vec<Position>,
+    pub walls: Vec<Wall>,
+    pub walls_left: Vec<u8>,
+    pub turn: PlayerID,
+}
+
+impl Board {
+    pub fn from_game(game: &Quoridor) -> Board {
+        let mut pawns: Vec<_> = game.pawns().iter().map(|(&id, &pos)| (id, pos)).collect();
+        pawns.sort_by_key(|(id, _)| *id);
+
+        Board {
+            size: game.size(),
+            pawns: pawns.into_iter().map(|(_, pos)| pos).collect(),
+            walls: game.walls().iter().copied().collect(),
+            walls_left: game.wall_counts().collect(),
+            turn: game.turn_of(),
+        }
+    }
+
+    pub fn player_count(&self) -> u8 {
+        self.pawns.len() as u8
+    }
+
+    /// The row `player` has to reach to win.
+    pub fn goal_row(&self, player: PlayerID) -> u8 {
+        if player == 0 {
+            self.size - 1
+        } else {
+            0
+        }
+    }
+
+    pub fn winner(&self) -> Option<PlayerID> {
+        (0..self.player_count()).find(|&p| self.pawns[p as usize].y == self.goal_row(p))
+    }
+
+    fn has_wall(&self, x: u8, y: u8, orientation: Orientation) -> bool {
+        self.walls
+            .iter()
+            .any(|w| w.position.x == x && w.position.y == y && w.orientation == orientation)
+    }
+
+    fn step(&self, pos: Position, (dx, dy): (i8, i8)) -> Option<Position> {
+        let x = pos.x as i8 + dx;
+        let y = pos.y as i8 + dy;
+        if x < 0 || y < 0 || x >= self.size as i8 || y >= self.size as i8 {
+            None
+        } else {
+            Some((x as u8, y as u8).into())
+        }
+    }
+
+    /// Whether a wall stands between two orthogonally adjacent squares.
+    pub fn is_blocked(&self, a: Position, b: Position) -> bool {
+        if a.x == b.x {
+            // A horizontal wall at (x, y) separates rows y - 1 and y.
+            let y = u8::max(a.y, b.y);
+            self.has_wall(a.x, y, Orientation::Horizontal)
+                || self.has_wall(a.x + 1, y, Orientation::Horizontal)
+        } else {
+            // A vertical wall at (x, y) separates columns x - 1 and x.
+            let x = u8::max(a.x, b.x);
+            self.has_wall(x, a.y, Orientation::Vertical)
+                || self.has_wall(x, a.y + 1, Orientation::Vertical)
+        }
+    }
+
+    fn open_step(&self, pos: Position, direction: (i8, i8)) -> Option<Position> {
+        self.step(pos, direction)
+            .filter(|&next| !self.is_blocked(pos, next))
+    }
+
+    fn is_occupied(&self, pos: Position) -> bool {
+        self.pawns.contains(&pos)
+    }
+
+    /// Squares the pawn of `player` can move to, including jumps.
+    pub fn pawn_moves(&self, player: PlayerID) -> Vec<Position> {
+        let from = self.pawns[player as usize];
+        let mut moves = vec![];
+        for &direction in DIRECTIONS.iter() {
+            let next = match self.open_step(from, direction) {
+                Some(next) => next,
+                None => continue,
+            };
+            if !self.is_occupied(next) {
+                moves.push(next);
+                continue;
+            }
+            match self.open_step(next, direction) {
+                Some(jump) if !self.is_occupied(jump) => moves.push(jump),
+                _ => {
+                    // Blocked straight jump, so the pawn may go around diagonally.
+                    let (dx, dy) = direction;
+                    for &side in [(dy, dx), (-dy, -dx)].iter() {
+                        if let Some(diagonal) = self.open_step(next, side) {
+                            if !self.is_occupied(diagonal) && !moves.contains(&diagonal) {
+                                moves.push(diagonal);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        moves
+    }
+
+    /// Shortest route for `player` to its goal row, ignoring other pawns.
+    pub fn path(&self, player: PlayerID) -> Option<Vec<Position>> {
+        let size = self.size as usize;
+        let index = |pos: Position| pos.y as usize * size + pos.x as usize;
+        let start = self.pawns[player as usize];
+        let goal = self.goal_row(player);
+
+        let mut previous: Vec<Option<Position>> = vec![None; size * size];
+        let mut seen = vec![false; size * size];
+        let mut queue = VecDeque::new();
+        seen[index(start)] = true;
+        queue.push_back(start);
+
+        while let Some(pos) = queue.pop_front() {
+            if pos.y == goal {
+                let mut path = vec![pos];
+                while let Some(prev) = previous[index(*path.last().unwrap())] {
+                    path.push(prev);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for &direction in DIRECTIONS.iter() {
+                if let Some(next) = self.open_step(pos, direction) {
+                    if !seen[index(next)] {
+                        seen[index(next)] = true;
+                        previous[index(next)] = Some(pos);
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Number of steps `player` needs to reach its goal row, if it can.
+    pub fn distance(&self, player: PlayerID) -> Option<u8> {
+        self.path(player).map(|path| path.len() as u8 - 1)
+    }
+
+    /// Whether a wall is on the board and doesn't overlap or cross another one.
+    pub fn wall_fits(&self, wall: &Wall) -> bool {
+        let (x, y) = (wall.position.x, wall.position.y);
+        if x == 0 || y == 0 || x >= self.size || y >= self.size {
+            return false;
+        }
+        if self.walls.iter().any(|w| w.position == wall.position) {
+            return false;
+        }
+        match wall.orientation {
+            Orientation::Horizontal => {
+                !self.has_wall(x - 1, y, Orientation::Horizontal)
+                    && !self.has_wall(x + 1, y, Orientation::Horizontal)
+            }
+            Orientation::Vertical => {
+                !self.has_wall(x, y - 1, Orientation::Vertical)
+                    && !self.has_wall(x, y + 1, Orientation::Vertical)
+            }
+        }
+    }
+
+    pub fn is_legal(&self, qmv: &Move) -> bool {
+        if self.winner().is_some() {
+            return false;
+        }
+        match qmv {
+            Move::MovePawn(from, to) => {
+                *from == self.pawns[self.turn as usize] && self.pawn_moves(self.turn).contains(to)
+            }
+            Move::PlaceWall(wall) => {
+                if self.walls_left[self.turn as usize] == 0 || !self.wall_fits(wall) {
+                    return false;
+                }
+                let mut next = self.clone();
+                next.walls.push(*wall);
+                (0..self.player_count()).all(|p| next.path(p).is_some())
+            }
+        }
+    }
+
+    /// Applies a move without checking it, see [`Board::is_legal`].
+    pub fn apply(&mut self, qmv: &Move) {
+        match qmv {
+            Move::MovePawn(_, to) => self.pawns[self.turn as usize] = *to,
+            Move::PlaceWall(wall) => {
+                self.walls.push(*wall);
+                self.walls_left[self.turn as usize] -= 1;
+            }
+        }
+        self.turn = (self.turn + 1) % self.player_count();
+    }
+
+    pub fn pawn_move_list(&self) -> Vec<Move> {
+        let from = self.pawns[self.turn as usize];
+        self.pawn_moves(self.turn)
+            .into_iter()
+            .map(|to| Move::MovePawn(from, to))
+            .collect()
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = self.pawn_move_list();
+        if self.walls_left[self.turn as usize] > 0 {
+            for x in 1..self.size {
+                for y in 1..self.size {
+                    for &orientation in [Orientation::Horizontal, Orientation::Vertical].iter() {
+                        let qmv = Move::PlaceWall(Wall {
+                            position: (x, y).into(),
+                            orientation,
+                            wall_type: WallType::Simple,
+                        });
+                        if self.is_legal(&qmv) {
+                            moves.push(qmv);
+                        }
+                    }
+                }
+            }
+        }
+        moves
+    }
+}
@@ -0,0 +1,63 @@
+//! Control messages about the connection rather than the game, such as rematch offers.
+//!
+//! They travel as JSON text frames on the game's websocket whichever format the
+//! game itself uses, tagged by `"type"` like the messages in [`crate::wire`]:
+//!
+//! ```json
+//! {"type": "seated", "side": 1}
+//! {"type": "players", "names": ["alice", null]}
+//! {"type": "rematch_offer"}
+//! {"type": "rematch_offered", "side": 1}
+//! {"type": "rematch_starting", "side": 0}
+//! {"type": "move_refused", "reason": "illegal move"}
+//! {"type": "game_ended", "winner": 0, "termination": "forfeit"}
+//! ```
+//!
+//! `seated` comes first, as soon as the connection has taken its seat, so the
+//! client knows its side before the game starts. `players` follows it, and again
+//! whenever someone else sits down or a rematch starts.
+//!
+//! `move_refused` answers a move the rulebook wouldn't play; the game carries
+//! on, waiting for another.
+//!
+//! `game_ended` comes for games the server ends rather than the rules, when a
+//! player sends a frame that can't be read or an engine runs out of time. No
+//! `game_end` follows in the game's own format then.
+//!
+//! Once a game has ended either player may offer a rematch; when every player
+//! has, a fresh game of the same type and size starts on the same connections
+//! with sides swapped, announced by `rematch_starting` and then a `game_start`.
+//! Offering again after the other player has is accepting.
+
+use crate::record::Termination;
+use serde::{Deserialize, Serialize};
+use tbmp_core::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientControl {
+    RematchOffer,
+    RematchDecline,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerControl {
+    /// This connection plays `side`.
+    Seated { side: PlayerID },
+    /// Nicknames by side, `None` for free seats and players who gave none.
+    Players { names: Vec<Option<String>> },
+    /// The player on `side` wants a rematch.
+    RematchOffered { side: PlayerID },
+    /// Someone declined or left, there won't be a rematch.
+    RematchDeclined,
+    /// A rematch is starting, with this connection now playing `side`.
+    RematchStarting { side: PlayerID },
+    /// The rulebook refused the move this connection sent, for `reason`.
+    MoveRefused { reason: String },
+    /// The server ended the game, see the module documentation.
+    GameEnded {
+        winner: Option<PlayerID>,
+        termination: Termination,
+    },
+}
@@ -0,0 +1,61 @@
+//! Correspondence games, played over days with a deadline for every move.
+//!
+//! The server keeps these games and serves them over plain HTTP rather than a
+//! websocket, so players can close the page and come back later. Whoever creates
+//! or joins one gets a token for their seat, which they send along to see the
+//! game as their side and to move:
+//!
+//! ```json
+//! {"token": "9f0c…", "ply": 12, "notation": "e5h"}
+//! ```
+//!
+//! `ply` is the number of moves played before this one, so a move sent twice or
+//! on a stale view of the game is refused rather than played again.
+
+use crate::record::Termination;
+use serde::{Deserialize, Serialize};
+use tbmp_core::*;
+
+/// The days per move a correspondence game can be created with.
+pub const DAYS_PER_MOVE: [u8; 4] = [1, 3, 7, 14];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub winner: Option<PlayerID>,
+    /// `Forfeit` when the player to move ran out of time.
+    pub termination: Termination,
+}
+
+/// A correspondence game, as seen by one of its players or anyone else.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameView {
+    pub id: String,
+    pub size: u8,
+    /// Nicknames by side, `None` for free seats and players who gave none.
+    pub players: Vec<Option<String>>,
+    /// Whether a seat is still free.
+    pub open: bool,
+    pub days_per_move: u8,
+    /// Every move so far in notation.
+    pub moves: Vec<String>,
+    /// The side the token sent along holds, if it holds one.
+    pub seat: Option<PlayerID>,
+    /// When the player to move runs out of time, in seconds since the Unix
+    /// epoch. Only running while every seat is taken and the game goes on.
+    pub deadline: Option<u64>,
+    pub outcome: Option<Outcome>,
+}
+
+impl GameView {
+    pub fn to_move(&self) -> PlayerID {
+        (self.moves.len() % self.players.len()) as PlayerID
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MoveRequest {
+    pub token: String,
+    /// The number of moves played before this one.
+    pub ply: usize,
+    pub notation: String,
+}
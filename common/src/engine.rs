@@ -0,0 +1,233 @@
+//! A UCI-style text protocol for driving quoridor engines over stdin/stdout.
+//!
+//! Every message is a single line. A session looks like this, `>` being sent to
+//! the engine and `<` read from it:
+//!
+//! ```text
+//! > uqi
+//! < id name Wallbot
+//! < id author Someone
+//! < uqiok
+//! > isready
+//! < readyok
+//! > newgame
+//! > position startpos moves e2 e8 e3h
+//! > go movetime 1000
+//! < info depth 3 score 2
+//! < bestmove d7
+//! > quit
+//! ```
+//!
+//! Moves use the algebraic notation from [`crate::notation`], `position` always
+//! lists every move since the start of the game, and `movetime` is the time the
+//! engine may think in milliseconds. Unknown lines are ignored on both sides.
+//!
+//! Games on boards other than the standard 9x9 announce their size as
+//! `newgame size 7`; a bare `newgame` is a standard board.
+
+use crate::board::BOARD_SIZE;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineCommand {
+    Uqi,
+    IsReady,
+    NewGame { size: u8 },
+    Position { moves: Vec<String> },
+    Go { movetime: u64 },
+    Stop,
+    Quit,
+}
+
+impl fmt::Display for EngineCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineCommand::Uqi => write!(f, "uqi"),
+            EngineCommand::IsReady => write!(f, "isready"),
+            EngineCommand::NewGame { size } if *size == BOARD_SIZE => write!(f, "newgame"),
+            EngineCommand::NewGame { size } => write!(f, "newgame size {}", size),
+            EngineCommand::Position { moves } if moves.is_empty() => write!(f, "position startpos"),
+            EngineCommand::Position { moves } => {
+                write!(f, "position startpos moves {}", moves.join(" "))
+            }
+            EngineCommand::Go { movetime } => write!(f, "go movetime {}", movetime),
+            EngineCommand::Stop => write!(f, "stop"),
+            EngineCommand::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+impl EngineCommand {
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        Some(match words.next()? {
+            "uqi" => EngineCommand::Uqi,
+            "isready" => EngineCommand::IsReady,
+            "newgame" => {
+                let size = match words.next() {
+                    Some("size") => words.next()?.parse().ok()?,
+                    Some(_) => return None,
+                    None => BOARD_SIZE,
+                };
+                EngineCommand::NewGame { size }
+            }
+            "position" => {
+                if words.next()? != "startpos" {
+                    return None;
+                }
+                let moves = match words.next() {
+                    Some("moves") => words.map(String::from).collect(),
+                    Some(_) => return None,
+                    None => vec![],
+                };
+                EngineCommand::Position { moves }
+            }
+            "go" => {
+                let mut movetime = None;
+                while let Some(word) = words.next() {
+                    if word == "movetime" {
+                        movetime = words.next()?.parse().ok();
+                    }
+                }
+                EngineCommand::Go {
+                    movetime: movetime?,
+                }
+            }
+            "stop" => EngineCommand::Stop,
+            "quit" => EngineCommand::Quit,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineReply {
+    Id { key: String, value: String },
+    UqiOk,
+    ReadyOk,
+    BestMove(String),
+    Info(String),
+}
+
+impl fmt::Display for EngineReply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineReply::Id { key, value } => write!(f, "id {} {}", key, value),
+            EngineReply::UqiOk => write!(f, "uqiok"),
+            EngineReply::ReadyOk => write!(f, "readyok"),
+            EngineReply::BestMove(qmv) => write!(f, "bestmove {}", qmv),
+            EngineReply::Info(info) => write!(f, "info {}", info),
+        }
+    }
+}
+
+impl EngineReply {
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        let (word, rest) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        Some(match word {
+            "id" => {
+                let mut parts = rest.splitn(2, char::is_whitespace);
+                EngineReply::Id {
+                    key: parts.next()?.into(),
+                    value: parts.next().unwrap_or("").trim().into(),
+                }
+            }
+            "uqiok" => EngineReply::UqiOk,
+            "readyok" => EngineReply::ReadyOk,
+            "bestmove" => EngineReply::BestMove(rest.split_whitespace().next()?.into()),
+            "info" => EngineReply::Info(rest.into()),
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_round_trip() {
+        let commands = vec![
+            (EngineCommand::Uqi, "uqi"),
+            (EngineCommand::IsReady, "isready"),
+            (EngineCommand::NewGame { size: BOARD_SIZE }, "newgame"),
+            (EngineCommand::NewGame { size: 7 }, "newgame size 7"),
+            (
+                EngineCommand::Position { moves: vec![] },
+                "position startpos",
+            ),
+            (
+                EngineCommand::Position {
+                    moves: vec!["e2".into(), "e8".into(), "e3h".into()],
+                },
+                "position startpos moves e2 e8 e3h",
+            ),
+            (EngineCommand::Go { movetime: 1000 }, "go movetime 1000"),
+            (EngineCommand::Stop, "stop"),
+            (EngineCommand::Quit, "quit"),
+        ];
+        for (command, line) in commands {
+            assert_eq!(command.to_string(), line);
+            assert_eq!(EngineCommand::parse(line), Some(command), "{}", line);
+        }
+    }
+
+    #[test]
+    fn commands_tolerate_extra_words() {
+        assert_eq!(
+            EngineCommand::parse("  go  wtime 5 movetime 250\n"),
+            Some(EngineCommand::Go { movetime: 250 })
+        );
+        for line in &[
+            "",
+            "go",
+            "go movetime soon",
+            "newgame 7",
+            "newgame size",
+            "position e2",
+            "position startpos e2",
+            "ponder",
+        ] {
+            assert_eq!(EngineCommand::parse(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn replies_round_trip() {
+        let replies = vec![
+            (
+                EngineReply::Id {
+                    key: "name".into(),
+                    value: "Wallbot 2".into(),
+                },
+                "id name Wallbot 2",
+            ),
+            (EngineReply::UqiOk, "uqiok"),
+            (EngineReply::ReadyOk, "readyok"),
+            (EngineReply::BestMove("d7".into()), "bestmove d7"),
+            (
+                EngineReply::Info("depth 3 score 2".into()),
+                "info depth 3 score 2",
+            ),
+        ];
+        for (reply, line) in replies {
+            assert_eq!(reply.to_string(), line);
+            assert_eq!(EngineReply::parse(line), Some(reply), "{}", line);
+        }
+    }
+
+    #[test]
+    fn replies_ignore_what_they_do_not_know() {
+        assert_eq!(
+            EngineReply::parse("bestmove e3h ponder e7\r\n"),
+            Some(EngineReply::BestMove("e3h".into()))
+        );
+        for line in &["", "bestmove", "option name Hash", "readyok!"] {
+            assert_eq!(EngineReply::parse(line), None, "{}", line);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Code shared between the quoridor_web client, server and tools.
+
+pub mod ai;
+pub mod board;
+pub mod control;
+pub mod correspondence;
+pub mod engine;
+pub mod notation;
+pub mod position;
+pub mod puzzle;
+pub mod record;
+pub mod wire;
+
+use quoridor_core::*;
+use tbmp_core::*;
+
+/// Returns the player that owns the given pawn.
+pub fn pawn_owner(game: &Quoridor, id: PawnID) -> PlayerID {
+    let pawns_per_player = game.get_pawn_count() / game.get_player_count();
+    id / pawns_per_player
+}
@@ -0,0 +1,254 @@
+//! Algebraic notation for quoridor moves.
+//!
+//! Columns are lettered from `a` and rows numbered from `1`, both starting at
+//! the corner nearest to player 0, so on a 9x9 board player 0 starts on `e1`.
+//!
+//! * A pawn move is written as its destination square (`e2`), or as origin and
+//!   destination (`e1e2`) when the mover has more than one pawn.
+//! * A wall is written as the square whose top right corner is the wall's
+//!   centre, followed by `h` or `v` for its orientation (`e3h`, `c5v`).
+
+use crate::{board::Board, pawn_owner};
+use quoridor_core::*;
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotationError {
+    Empty,
+    InvalidSquare(String),
+    InvalidWall(String),
+    NoPawn,
+    AmbiguousPawn,
+}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NotationError::Empty => write!(f, "empty move"),
+            NotationError::InvalidSquare(s) => write!(f, "invalid square '{}'", s),
+            NotationError::InvalidWall(s) => write!(f, "invalid wall '{}'", s),
+            NotationError::NoPawn => write!(f, "no pawn to move"),
+            NotationError::AmbiguousPawn => {
+                write!(f, "more than one pawn can move, use the long form (e.g. e1e2)")
+            }
+        }
+    }
+}
+
+impl Error for NotationError {}
+
+pub fn format_square(pos: Position) -> String {
+    format!("{}{}", (b'a' + pos.x) as char, pos.y as u16 + 1)
+}
+
+pub fn parse_square(s: &str) -> Result<Position, NotationError> {
+    let invalid = || NotationError::InvalidSquare(s.into());
+    let mut chars = s.chars();
+    let column = chars.next().ok_or_else(invalid)?;
+    if !column.is_ascii_lowercase() {
+        return Err(invalid());
+    }
+    // `u8::from_str` would take a sign too, as in `e+5`.
+    let digits = chars.as_str();
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let row: u8 = digits.parse().map_err(|_| invalid())?;
+    if row == 0 {
+        return Err(invalid());
+    }
+    Ok((column as u8 - b'a', row - 1).into())
+}
+
+/// Formats a wall. A wall centred on the board's edge, which no move can place,
+/// has no square to be written as and comes out as its centre in brackets, which
+/// `parse_wall` refuses.
+pub fn format_wall(wall: &Wall) -> String {
+    let orientation = match wall.orientation {
+        Orientation::Horizontal => 'h',
+        Orientation::Vertical => 'v',
+    };
+    let (x, y) = (wall.position.x, wall.position.y);
+    match (x.checked_sub(1), y.checked_sub(1)) {
+        (Some(x), Some(y)) => format!("{}{}", format_square((x, y).into()), orientation),
+        _ => format!("({},{}){}", x, y, orientation),
+    }
+}
+
+pub fn parse_wall(s: &str) -> Result<Wall, NotationError> {
+    let invalid = || NotationError::InvalidWall(s.into());
+    let (last, _) = s.char_indices().last().ok_or_else(invalid)?;
+    let (square, orientation) = s.split_at(last);
+    let orientation = match orientation {
+        "h" => Orientation::Horizontal,
+        "v" => Orientation::Vertical,
+        _ => return Err(invalid()),
+    };
+    let square = parse_square(square).map_err(|_| invalid())?;
+    Ok(Wall {
+        position: (square.x + 1, square.y + 1).into(),
+        orientation,
+        wall_type: WallType::Simple,
+    })
+}
+
+/// Formats a move in short algebraic notation.
+pub fn format_move(qmv: &Move) -> String {
+    match qmv {
+        Move::MovePawn(_, to) => format_square(*to),
+        Move::PlaceWall(wall) => format_wall(wall),
+    }
+}
+
+/// Parses a move for the player whose turn it is in `game`.
+///
+/// This only resolves the notation, legality is left to the rulebook.
+pub fn parse_move(s: &str, game: &Quoridor) -> Result<Move, NotationError> {
+    let own_pawns = game
+        .pawns()
+        .iter()
+        .filter(|(&id, _)| pawn_owner(game, id) == game.turn_of())
+        .map(|(_, &pos)| pos);
+    parse_move_from(s, own_pawns)
+}
+
+/// Like [`parse_move`], for the side to move on `board`.
+pub fn parse_board_move(s: &str, board: &Board) -> Result<Move, NotationError> {
+    parse_move_from(s, std::iter::once(board.pawns[board.turn as usize]))
+}
+
+/// Parses a move by the player owning `own_pawns`, which short pawn moves start from.
+fn parse_move_from(
+    s: &str,
+    mut own_pawns: impl Iterator<Item = Position>,
+) -> Result<Move, NotationError> {
+    let s = s.trim().to_ascii_lowercase();
+    if s.is_empty() {
+        return Err(NotationError::Empty);
+    }
+    if s.ends_with('h') || s.ends_with('v') {
+        return Ok(Move::PlaceWall(parse_wall(&s)?));
+    }
+
+    // The long form has a second column letter after the first square.
+    if let Some(split) = s.char_indices().skip(1).find(|(_, c)| c.is_ascii_lowercase()) {
+        let (from, to) = s.split_at(split.0);
+        return Ok(Move::MovePawn(parse_square(from)?, parse_square(to)?));
+    }
+
+    let to = parse_square(&s)?;
+    let from = own_pawns.next().ok_or(NotationError::NoPawn)?;
+    if own_pawns.next().is_some() {
+        return Err(NotationError::AmbiguousPawn);
+    }
+    Ok(Move::MovePawn(from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board() -> Board {
+        Board {
+            size: 9,
+            pawns: vec![(4, 0).into(), (4, 8).into()],
+            walls: vec![],
+            walls_left: vec![10, 10],
+            turn: 0,
+        }
+    }
+
+    fn wall(x: u8, y: u8, orientation: Orientation) -> Wall {
+        Wall {
+            position: (x, y).into(),
+            orientation,
+            wall_type: WallType::Simple,
+        }
+    }
+
+    #[test]
+    fn squares() {
+        assert_eq!(parse_square("e1"), Ok((4, 0).into()));
+        assert_eq!(parse_square("a10"), Ok((0, 9).into()));
+        assert_eq!(format_square((4, 0).into()), "e1");
+        assert_eq!(format_square((10, 10).into()), "k11");
+        for square in &["a1", "e5", "i9", "k11"] {
+            assert_eq!(format_square(parse_square(square).unwrap()), *square);
+        }
+    }
+
+    #[test]
+    fn invalid_squares() {
+        for square in &["", "e", "5", "e0", "e+5", "e-5", "e 5", "E5", "e5x", "e256"] {
+            assert_eq!(
+                parse_square(square),
+                Err(NotationError::InvalidSquare(square.to_string())),
+                "{}",
+                square
+            );
+        }
+    }
+
+    #[test]
+    fn walls() {
+        let h = wall(5, 3, Orientation::Horizontal);
+        assert_eq!(format_wall(&h), "e3h");
+        assert_eq!(parse_wall("e3h"), Ok(h));
+        let v = wall(3, 5, Orientation::Vertical);
+        assert_eq!(format_wall(&v), "c5v");
+        assert_eq!(parse_wall("c5v"), Ok(v));
+        for s in &["", "h", "e3", "e3x", "e+3h", "e0v"] {
+            assert!(parse_wall(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn walls_ending_in_wide_characters() {
+        for s in &["e5é", "é", "e5h€"] {
+            assert!(parse_wall(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn walls_on_the_edge() {
+        for &(x, y) in &[(0, 3), (3, 0), (0, 0)] {
+            let text = format_wall(&wall(x, y, Orientation::Vertical));
+            assert!(parse_wall(&text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn moves() {
+        let board = board();
+        match parse_board_move("e2", &board) {
+            Ok(Move::MovePawn(from, to)) => {
+                assert_eq!((from, to), ((4, 0).into(), (4, 1).into()))
+            }
+            other => panic!("{:?}", other.map(|m| format_move(&m))),
+        }
+        match parse_board_move(" E1E2 ", &board) {
+            Ok(Move::MovePawn(from, to)) => {
+                assert_eq!((from, to), ((4, 0).into(), (4, 1).into()))
+            }
+            other => panic!("{:?}", other.map(|m| format_move(&m))),
+        }
+        match parse_board_move("e3h", &board) {
+            Ok(Move::PlaceWall(w)) => assert_eq!(w, wall(5, 3, Orientation::Horizontal)),
+            other => panic!("{:?}", other.map(|m| format_move(&m))),
+        }
+        assert_eq!(
+            parse_board_move("", &board).err(),
+            Some(NotationError::Empty)
+        );
+        assert!(parse_board_move("e+2", &board).is_err());
+    }
+
+    #[test]
+    fn moves_round_trip() {
+        let board = board();
+        for text in &["e2", "d1", "a1h", "h8v"] {
+            let qmv = parse_board_move(text, &board).unwrap();
+            assert_eq!(format_move(&qmv), *text);
+        }
+    }
+}
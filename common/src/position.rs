@@ -0,0 +1,353 @@
+//! A one-line text form of a [`Board`], for sharing and setting up positions.
+//!
+//! A position is five fields separated by spaces: the board size, the pawns by
+//! player, the walls, the walls each player has left, and the player to move.
+//! Squares and walls are in [notation], lists are separated by commas and `-`
+//! stands for no walls. The start of a standard game is
+//!
+//! ```text
+//! 9 e1,e9 - 10,10 0
+//! ```
+//!
+//! [notation]: crate::notation
+
+use crate::{
+    board::{Board, BOARD_SIZES},
+    notation::{format_square, format_wall, parse_square, parse_wall, NotationError},
+};
+use quoridor_core::*;
+use std::{error::Error, fmt, str::FromStr};
+use tbmp_core::*;
+
+/// More than enough plies to set up any position on the largest board.
+const MAX_SETUP_PLIES: usize = 2000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PositionError {
+    /// Not the five fields, or a field that isn't a number where one should be.
+    Malformed,
+    UnsupportedSize(u8),
+    Notation(NotationError),
+    /// Only two player positions can be described.
+    PlayerCount,
+    /// A pawn off the board or on top of another.
+    PawnPlacement(String),
+    /// A wall off the board or crossing or overlapping another.
+    WallPlacement(String),
+    /// Walls that leave a pawn without a way to its goal.
+    NoPath(PlayerID),
+    InvalidTurn(PlayerID),
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::Malformed => write!(
+                f,
+                "expected size, pawns, walls, walls left and side to move, e.g. 9 e1,e9 - 10,10 0"
+            ),
+            PositionError::UnsupportedSize(size) => write!(f, "unsupported board size {}", size),
+            PositionError::Notation(e) => write!(f, "{}", e),
+            PositionError::PlayerCount => write!(f, "a position needs two players"),
+            PositionError::PawnPlacement(s) => write!(f, "a pawn can't stand on {}", s),
+            PositionError::WallPlacement(s) => write!(f, "wall {} doesn't fit", s),
+            PositionError::NoPath(player) => {
+                write!(f, "player {} has no way to their goal", player)
+            }
+            PositionError::InvalidTurn(player) => write!(f, "there is no player {}", player),
+        }
+    }
+}
+
+impl Error for PositionError {}
+
+impl From<NotationError> for PositionError {
+    fn from(e: NotationError) -> Self {
+        PositionError::Notation(e)
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |items: Vec<String>| items.join(",");
+        let pawns = join(self.pawns.iter().map(|&p| format_square(p)).collect());
+        let walls = match join(self.walls.iter().map(format_wall).collect()) {
+            walls if walls.is_empty() => String::from("-"),
+            walls => walls,
+        };
+        let walls_left = join(self.walls_left.iter().map(u8::to_string).collect());
+        write!(
+            f,
+            "{} {} {} {} {}",
+            self.size, pawns, walls, walls_left, self.turn
+        )
+    }
+}
+
+impl FromStr for Board {
+    type Err = PositionError;
+
+    fn from_str(s: &str) -> Result<Board, PositionError> {
+        let fields: Vec<_> = s.split_whitespace().collect();
+        let (size, pawns, walls, walls_left, turn) = match fields[..] {
+            [size, pawns, walls, walls_left, turn] => (size, pawns, walls, walls_left, turn),
+            _ => return Err(PositionError::Malformed),
+        };
+        let number = |s: &str| s.parse::<u8>().map_err(|_| PositionError::Malformed);
+
+        let size = number(size)?;
+        if !BOARD_SIZES.contains(&size) {
+            return Err(PositionError::UnsupportedSize(size));
+        }
+        let mut board = Board {
+            size,
+            pawns: vec![],
+            walls: vec![],
+            walls_left: walls_left
+                .split(',')
+                .map(number)
+                .collect::<Result<_, _>>()?,
+            turn: number(turn)?,
+        };
+
+        for square in pawns.split(',') {
+            let pos = parse_square(square)?;
+            if pos.x >= size || pos.y >= size || board.pawns.contains(&pos) {
+                return Err(PositionError::PawnPlacement(square.into()));
+            }
+            board.pawns.push(pos);
+        }
+        if board.pawns.len() != 2 || board.walls_left.len() != 2 {
+            return Err(PositionError::PlayerCount);
+        }
+        if board.turn >= board.player_count() {
+            return Err(PositionError::InvalidTurn(board.turn));
+        }
+
+        for notation in walls.split(',').filter(|&w| w != "-") {
+            let wall = parse_wall(notation)?;
+            if !board.wall_fits(&wall) {
+                return Err(PositionError::WallPlacement(notation.into()));
+            }
+            board.walls.push(wall);
+        }
+        if let Some(player) = (0..board.player_count()).find(|&p| board.path(p).is_none()) {
+            return Err(PositionError::NoPath(player));
+        }
+        Ok(board)
+    }
+}
+
+/// The squares next to `pos` with no wall in between.
+fn neighbours(board: &Board, pos: Position) -> Vec<Position> {
+    let (x, y) = (pos.x as i8, pos.y as i8);
+    [(x, y + 1), (x + 1, y), (x, y - 1), (x - 1, y)]
+        .iter()
+        .filter(|&&(x, y)| x >= 0 && y >= 0 && x < board.size as i8 && y < board.size as i8)
+        .map(|&(x, y)| Position::from((x as u8, y as u8)))
+        .filter(|&next| !board.is_blocked(pos, next))
+        .collect()
+}
+
+/// Whether `player`'s pawn may step onto `pos` while setting up: it's free and
+/// not on their goal row, which would end the game.
+fn can_enter(board: &Board, player: usize, pos: Position) -> bool {
+    !board.pawns.contains(&pos) && pos.y != board.goal_row(player as PlayerID)
+}
+
+/// The first step on a shortest walk of `player`'s pawn to `to`, going around
+/// the other pawns.
+fn step_toward(board: &Board, player: usize, to: Position) -> Option<Move> {
+    let from = board.pawns[player];
+    let size = board.size as usize;
+    let index = |pos: Position| pos.y as usize * size + pos.x as usize;
+    let mut first: Vec<Option<Position>> = vec![None; size * size];
+    let mut queue = std::collections::VecDeque::new();
+    queue.push_back(from);
+    while let Some(pos) = queue.pop_front() {
+        if pos == to {
+            return Some(Move::MovePawn(from, first[index(pos)]?));
+        }
+        for next in neighbours(board, pos) {
+            if next != from && first[index(next)].is_none() && can_enter(board, player, next) {
+                first[index(next)] = Some(if pos == from {
+                    next
+                } else {
+                    first[index(pos)]?
+                });
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// A step off the square `player`'s pawn is on, to come back to next turn.
+/// None of the walls still to be placed may come between, so it always can.
+fn step_aside(board: &Board, player: usize, walls: &[Wall]) -> Option<Move> {
+    let from = board.pawns[player];
+    let blocks = |wall: &Wall, next: Position| {
+        let board = Board {
+            walls: vec![*wall],
+            ..board.clone()
+        };
+        board.is_blocked(from, next)
+    };
+    neighbours(board, from)
+        .into_iter()
+        .find(|&next| can_enter(board, player, next) && !walls.iter().any(|w| blocks(w, next)))
+        .map(|next| Move::MovePawn(from, next))
+}
+
+/// Moves that lead from `start`, the start of a game, to `target`, if this can
+/// find any. Pawns walk to their squares first, then the walls go up, pawns
+/// stepping aside and back while the other player places theirs.
+///
+/// Pawns only make single steps, each changing the colour of their square on a
+/// chequered board, so the side to move has to match how far the pawns walked
+/// and how many walls went up. Positions that need a jump to reach aren't found.
+pub fn setup_moves(start: &Board, target: &Board) -> Option<Vec<Move>> {
+    let players = start.pawns.len();
+    if target.size != start.size || target.pawns.len() != players || target.winner().is_some() {
+        return None;
+    }
+    // The walls each player still has to place.
+    let mut to_place = (0..players)
+        .map(|p| start.walls_left[p].checked_sub(target.walls_left[p]))
+        .collect::<Option<Vec<_>>>()?;
+    if to_place.iter().map(|&n| n as usize).sum::<usize>() != target.walls.len() {
+        return None;
+    }
+    let plies: usize = (0..players)
+        .map(|p| {
+            let (a, b) = (start.pawns[p], target.pawns[p]);
+            let walk = (a.x as i16 - b.x as i16).abs() + (a.y as i16 - b.y as i16).abs();
+            walk as usize + to_place[p] as usize
+        })
+        .sum();
+    if plies % players != target.turn as usize {
+        return None;
+    }
+
+    let mut board = start.clone();
+    let mut walls = target.walls.clone();
+    let mut moves = vec![];
+    let mut walls_up = false;
+    for _ in 0..MAX_SETUP_PLIES {
+        let home = board.pawns == target.pawns;
+        if home && walls.is_empty() {
+            return Some(moves);
+        }
+        walls_up |= home;
+        let me = board.turn as usize;
+        let qmv = if board.pawns[me] != target.pawns[me] {
+            step_toward(&board, me, target.pawns[me]).or_else(|| step_aside(&board, me, &[]))?
+        } else if walls_up && to_place[me] > 0 {
+            let i = walls
+                .iter()
+                .position(|w| board.is_legal(&Move::PlaceWall(*w)))?;
+            to_place[me] -= 1;
+            Move::PlaceWall(walls.remove(i))
+        } else {
+            step_aside(&board, me, &walls)?
+        };
+        board.apply(&qmv);
+        moves.push(qmv);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: &str = "9 e1,e9 - 10,10 0";
+
+    fn position(s: &str) -> Board {
+        s.parse().unwrap()
+    }
+
+    /// Plays `moves` out from `start`, checking each is legal on the way.
+    fn replay(start: &Board, moves: &[Move]) -> Board {
+        let mut board = start.clone();
+        for qmv in moves {
+            assert!(board.is_legal(qmv), "illegal setup move {:?}", qmv);
+            board.apply(qmv);
+        }
+        board
+    }
+
+    /// Whether two boards are the same position, whatever order the walls went up in.
+    fn same_position(a: &Board, b: &Board) -> bool {
+        a.size == b.size
+            && a.pawns == b.pawns
+            && a.walls_left == b.walls_left
+            && a.turn == b.turn
+            && a.walls.len() == b.walls.len()
+            && a.walls.iter().all(|w| b.walls.contains(w))
+    }
+
+    #[test]
+    fn round_trip() {
+        for s in &[START, "9 e5,d6 c3h,e3v,f7h 8,9 1", "9 a2,i8 a1h,h8v 9,9 0"] {
+            let board = position(s);
+            assert_eq!(board.to_string(), *s);
+            assert_eq!(position(&board.to_string()), board);
+        }
+        assert_eq!(position(START).walls, vec![]);
+        assert_eq!(position(START).walls_left, vec![10, 10]);
+    }
+
+    #[test]
+    fn rejections() {
+        let error = |s: &str| s.parse::<Board>().unwrap_err();
+        assert_eq!(error(""), PositionError::Malformed);
+        assert_eq!(error("9 e1,e9 - 10,10"), PositionError::Malformed);
+        assert_eq!(error("9 e1,e9 - 10,10 0 0"), PositionError::Malformed);
+        assert_eq!(error("9 e1,e9 - 10,x 0"), PositionError::Malformed);
+        assert_eq!(
+            error("8 e1,e8 - 10,10 0"),
+            PositionError::UnsupportedSize(8)
+        );
+        assert_eq!(
+            error("9 e1,e1 - 10,10 0"),
+            PositionError::PawnPlacement("e1".into())
+        );
+        assert_eq!(error("9 e1 - 10 0"), PositionError::PlayerCount);
+        // Walls on the same spot cross; walls a column apart overlap.
+        assert_eq!(
+            error("9 e1,e9 e3h,e3v 10,10 0"),
+            PositionError::WallPlacement("e3v".into())
+        );
+        assert_eq!(
+            error("9 e1,e9 e3h,f3h 10,10 0"),
+            PositionError::WallPlacement("f3h".into())
+        );
+        // a1 and b1 walled in together.
+        assert_eq!(error("9 a1,e9 a1h,b1v 10,10 0"), PositionError::NoPath(0));
+        assert_eq!(error("9 e1,e9 - 10,10 2"), PositionError::InvalidTurn(2));
+    }
+
+    #[test]
+    fn setup_moves_reach_the_target() {
+        let start = position(START);
+        for s in &["9 e3,e7 - 10,10 0", "9 e3,d7 c5h,f4v 9,9 1", START] {
+            let target = position(s);
+            let moves = setup_moves(&start, &target).unwrap();
+            let reached = replay(&start, &moves);
+            assert!(same_position(&reached, &target), "{} gave {}", s, reached);
+        }
+    }
+
+    #[test]
+    fn setup_moves_need_the_right_side_to_move() {
+        let start = position(START);
+        assert_eq!(setup_moves(&start, &position("9 e3,e7 - 10,10 1")), None);
+        assert_eq!(
+            setup_moves(&start, &position("9 e3,d7 c5h,f4v 9,9 0")),
+            None
+        );
+        // More walls up than were ever placed.
+        assert_eq!(setup_moves(&start, &position("9 e3,e7 c5h 10,10 0")), None);
+    }
+}
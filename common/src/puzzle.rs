@@ -0,0 +1,177 @@
+//! Puzzles: standard positions where the side to move has a line to find.
+//!
+//! A puzzle file holds puzzles separated by blank lines, each made of
+//! `key: value` lines. Lines starting with `#` are comments.
+//!
+//! ```text
+//! id: race-1
+//! title: Win the race
+//! moves: e2 d9 e3 d8 e4 d7 e5 d6 e6 d5 e7 d4
+//! solution: e8 d3 e9
+//! ```
+//!
+//! * `id` names the puzzle in URLs and has to be unique.
+//! * `title` is optional and defaults to the id.
+//! * `size` is the board size, 9 if left out.
+//! * `moves` sets the position up from the start of a game, in [notation].
+//! * `solution` is a line of play for the solver, the side to move after
+//!   `moves`, with the opponent's replies in between, so it ends on the
+//!   solver's move. Repeat it for every line that counts as solving the puzzle.
+//!
+//! [notation]: crate::notation
+
+use crate::{
+    board::{Board, BOARD_SIZE, BOARD_SIZES},
+    notation::parse_board_move,
+};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, fmt};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Puzzle {
+    pub id: String,
+    pub title: String,
+    pub size: u8,
+    /// The moves leading to the position, from the start of a game.
+    pub moves: Vec<String>,
+    /// Every accepted line, alternating between the solver and the opponent.
+    pub solutions: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PuzzleError {
+    /// A line that can't be read, by line number.
+    Syntax(usize, String),
+    /// A puzzle without a required key, named by its id or first line.
+    Missing(String, &'static str),
+    DuplicateId(String),
+    /// A move in a puzzle that can't be played where it stands.
+    IllegalMove {
+        id: String,
+        notation: String,
+    },
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PuzzleError::Syntax(line, message) => write!(f, "line {}: {}", line, message),
+            PuzzleError::Missing(puzzle, key) => write!(f, "puzzle {} has no {}", puzzle, key),
+            PuzzleError::DuplicateId(id) => write!(f, "more than one puzzle is called {}", id),
+            PuzzleError::IllegalMove { id, notation } => {
+                write!(f, "puzzle {}: {} can't be played there", id, notation)
+            }
+        }
+    }
+}
+
+impl Error for PuzzleError {}
+
+fn split_moves(value: &str) -> Vec<String> {
+    value.split_whitespace().map(String::from).collect()
+}
+
+/// Reads a puzzle file, see the module documentation for the format.
+pub fn parse(text: &str) -> Result<Vec<Puzzle>, PuzzleError> {
+    let mut puzzles: Vec<Puzzle> = vec![];
+    let mut current: Option<(usize, Puzzle)> = None;
+
+    // A trailing empty line closes the last puzzle.
+    for (number, line) in text.lines().chain(std::iter::once("")).enumerate() {
+        let number = number + 1;
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if line.is_empty() {
+            if let Some((start, puzzle)) = current.take() {
+                if puzzle.id.is_empty() {
+                    return Err(PuzzleError::Missing(format!("at line {}", start), "id"));
+                }
+                if puzzle.solutions.is_empty() {
+                    return Err(PuzzleError::Missing(puzzle.id, "solution"));
+                }
+                if puzzles.iter().any(|p| p.id == puzzle.id) {
+                    return Err(PuzzleError::DuplicateId(puzzle.id));
+                }
+                puzzles.push(puzzle);
+            }
+            continue;
+        }
+
+        let syntax = |message: &str| PuzzleError::Syntax(number, message.into());
+        let mut kv = line.splitn(2, ':');
+        let (key, value) = match (kv.next(), kv.next()) {
+            (Some(key), Some(value)) => (key.trim(), value.trim()),
+            _ => return Err(syntax("expected `key: value`")),
+        };
+        let (_, puzzle) = current.get_or_insert_with(|| {
+            let puzzle = Puzzle {
+                id: String::new(),
+                title: String::new(),
+                size: BOARD_SIZE,
+                moves: vec![],
+                solutions: vec![],
+            };
+            (number, puzzle)
+        });
+        match key {
+            "id" => {
+                if value.is_empty() || value.contains(|c: char| c == '/' || c.is_whitespace()) {
+                    return Err(syntax("an id can't be empty or contain spaces or slashes"));
+                }
+                puzzle.id = value.into();
+                if puzzle.title.is_empty() {
+                    puzzle.title = value.into();
+                }
+            }
+            "title" => puzzle.title = value.into(),
+            "size" => {
+                puzzle.size = value
+                    .parse()
+                    .ok()
+                    .filter(|size| BOARD_SIZES.contains(size))
+                    .ok_or_else(|| syntax("unsupported board size"))?;
+            }
+            "moves" => puzzle.moves = split_moves(value),
+            "solution" => {
+                let solution = split_moves(value);
+                if solution.len() % 2 == 0 {
+                    return Err(syntax("a solution has to end on the solver's move"));
+                }
+                puzzle.solutions.push(solution);
+            }
+            _ => return Err(syntax(&format!("unknown key `{}`", key))),
+        }
+    }
+    Ok(puzzles)
+}
+
+impl Puzzle {
+    /// Plays `moves` and every solution out from `start`, the starting position
+    /// on the puzzle's board, making sure each move is legal.
+    pub fn check(&self, start: &Board) -> Result<(), PuzzleError> {
+        let play = |board: &mut Board, notation: &String| match parse_board_move(notation, board) {
+            Ok(qmv) if board.is_legal(&qmv) => {
+                board.apply(&qmv);
+                Ok(())
+            }
+            _ => Err(PuzzleError::IllegalMove {
+                id: self.id.clone(),
+                notation: notation.clone(),
+            }),
+        };
+
+        let mut position = start.clone();
+        for notation in &self.moves {
+            play(&mut position, notation)?;
+        }
+        for solution in &self.solutions {
+            let mut board = position.clone();
+            for notation in solution {
+                play(&mut board, notation)?;
+            }
+        }
+        Ok(())
+    }
+}
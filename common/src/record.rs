@@ -0,0 +1,33 @@
+//! Records of finished games, as written to disk by the tools.
+
+use crate::board::BOARD_SIZE;
+use serde::{Deserialize, Serialize};
+use tbmp_core::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Termination {
+    /// A pawn reached its goal row.
+    Goal,
+    /// The game went on for too long and was called a draw.
+    MoveLimit,
+    /// A player left, sent an illegal move or ran out of time.
+    Forfeit,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub game_type: String,
+    #[serde(default = "default_size")]
+    pub size: u8,
+    /// Player names, indexed by side.
+    pub players: Vec<String>,
+    /// Every move in algebraic notation.
+    pub moves: Vec<String>,
+    pub winner: Option<PlayerID>,
+    pub termination: Termination,
+}
+
+fn default_size() -> u8 {
+    BOARD_SIZE
+}
@@ -0,0 +1,321 @@
+//! JSON wire protocol, for clients that don't share the server's bincode types.
+//!
+//! Join a lobby with `ws://HOST/join/{name}?format=json` and every websocket
+//! message becomes a text frame holding one JSON object, tagged by `"type"`.
+//!
+//! Boards are square, `size` squares wide as given in `game_start`. Coordinates
+//! are zero based and start from player 0's left corner, so on a 9x9 board
+//! player 0 starts on `{"x": 4, "y": 0}`. A wall's `x`/`y` is the grid
+//! intersection at its centre, `1..size`; a horizontal wall at `(x, y)` lies
+//! between rows `y - 1` and `y`, covering columns `x - 1` and `x`, and a vertical
+//! one lies between columns `x - 1` and `x`, covering rows `y - 1` and `y`.
+//!
+//! Client to server, a [`WireMove`]:
+//!
+//! ```json
+//! {"type": "move_pawn", "from": {"x": 4, "y": 0}, "to": {"x": 4, "y": 1}}
+//! {"type": "place_wall", "wall": {"x": 5, "y": 3, "orientation": "horizontal"}}
+//! {"type": "algebraic", "notation": "e3h"}
+//! ```
+//!
+//! Server to client, a [`WireEvent`]:
+//!
+//! ```json
+//! {"type": "game_start", "side": 0, "state": {"size": 9, "turn": 0, "walls_left": [10, 10],
+//!     "pawns": [{"id": 0, "owner": 0, "position": {"x": 4, "y": 0}}, ...], "walls": []}}
+//! {"type": "move", "player": 0, "move": {"type": "move_pawn", ...}, "notation": "e2"}
+//! {"type": "game_end", "winner": 1}
+//! {"type": "opponent_quit"}
+//! {"type": "error", "message": "invalid square 'z0'"}
+//! ```
+//!
+//! Control messages from [`crate::control`], such as rematch offers, share the
+//! same text frames.
+//!
+//! `winner` is `null` for a draw. Fields may be added to existing messages, but
+//! existing fields and messages keep their meaning.
+
+use crate::{
+    board::GameSize,
+    notation::{parse_move, NotationError},
+    pawn_owner,
+};
+use quoridor_core::*;
+use serde::{Deserialize, Serialize};
+use tbmp_core::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WirePosition {
+    pub x: u8,
+    pub y: u8,
+}
+
+impl From<Position> for WirePosition {
+    fn from(pos: Position) -> Self {
+        WirePosition { x: pos.x, y: pos.y }
+    }
+}
+
+impl From<WirePosition> for Position {
+    fn from(pos: WirePosition) -> Self {
+        (pos.x, pos.y).into()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WireOrientation {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct WireWall {
+    pub x: u8,
+    pub y: u8,
+    pub orientation: WireOrientation,
+}
+
+impl From<&Wall> for WireWall {
+    fn from(wall: &Wall) -> Self {
+        WireWall {
+            x: wall.position.x,
+            y: wall.position.y,
+            orientation: match wall.orientation {
+                Orientation::Horizontal => WireOrientation::Horizontal,
+                Orientation::Vertical => WireOrientation::Vertical,
+            },
+        }
+    }
+}
+
+impl From<WireWall> for Wall {
+    fn from(wall: WireWall) -> Self {
+        Wall {
+            position: (wall.x, wall.y).into(),
+            orientation: match wall.orientation {
+                WireOrientation::Horizontal => Orientation::Horizontal,
+                WireOrientation::Vertical => Orientation::Vertical,
+            },
+            wall_type: WallType::Simple,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WireMove {
+    MovePawn { from: WirePosition, to: WirePosition },
+    PlaceWall { wall: WireWall },
+    Algebraic { notation: String },
+}
+
+impl From<&Move> for WireMove {
+    fn from(qmv: &Move) -> Self {
+        match qmv {
+            Move::MovePawn(from, to) => WireMove::MovePawn {
+                from: (*from).into(),
+                to: (*to).into(),
+            },
+            Move::PlaceWall(wall) => WireMove::PlaceWall { wall: wall.into() },
+        }
+    }
+}
+
+impl WireMove {
+    /// Resolves the move for the player whose turn it is in `game`.
+    pub fn into_move(self, game: &Quoridor) -> Result<Move, NotationError> {
+        Ok(match self {
+            WireMove::MovePawn { from, to } => Move::MovePawn(from.into(), to.into()),
+            WireMove::PlaceWall { wall } => Move::PlaceWall(wall.into()),
+            WireMove::Algebraic { notation } => parse_move(&notation, game)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WirePawn {
+    pub id: PawnID,
+    pub owner: PlayerID,
+    pub position: WirePosition,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WireState {
+    pub size: u8,
+    pub turn: PlayerID,
+    pub pawns: Vec<WirePawn>,
+    pub walls: Vec<WireWall>,
+    pub walls_left: Vec<u8>,
+}
+
+impl From<&Quoridor> for WireState {
+    fn from(game: &Quoridor) -> Self {
+        let mut pawns: Vec<_> = game
+            .pawns()
+            .iter()
+            .map(|(&id, &pos)| WirePawn {
+                id,
+                owner: pawn_owner(game, id),
+                position: pos.into(),
+            })
+            .collect();
+        pawns.sort_by_key(|pawn| pawn.id);
+
+        WireState {
+            size: game.size(),
+            turn: game.turn_of(),
+            pawns,
+            walls: game.walls().iter().map(WireWall::from).collect(),
+            walls_left: game.wall_counts().collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WireEvent {
+    GameStart {
+        side: PlayerID,
+        state: WireState,
+    },
+    Move {
+        player: PlayerID,
+        #[serde(rename = "move")]
+        qmove: WireMove,
+        notation: String,
+    },
+    GameEnd {
+        winner: Option<PlayerID>,
+    },
+    OpponentQuit,
+    Error {
+        message: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use std::fmt::Debug;
+
+    /// Parses `json`, checking it comes back the same through the wire format.
+    fn round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(json: &str) -> T {
+        let value: T = serde_json::from_str(json).unwrap();
+        let text = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<T>(&text).unwrap(), value);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+            serde_json::from_str::<serde_json::Value>(json).unwrap(),
+        );
+        value
+    }
+
+    #[test]
+    fn moves() {
+        assert_eq!(
+            round_trip::<WireMove>(
+                r#"{"type": "move_pawn", "from": {"x": 4, "y": 0}, "to": {"x": 4, "y": 1}}"#
+            ),
+            WireMove::MovePawn {
+                from: WirePosition { x: 4, y: 0 },
+                to: WirePosition { x: 4, y: 1 },
+            }
+        );
+        assert_eq!(
+            round_trip::<WireMove>(
+                r#"{"type": "place_wall", "wall": {"x": 5, "y": 3, "orientation": "horizontal"}}"#
+            ),
+            WireMove::PlaceWall {
+                wall: WireWall {
+                    x: 5,
+                    y: 3,
+                    orientation: WireOrientation::Horizontal,
+                },
+            }
+        );
+        assert_eq!(
+            round_trip::<WireMove>(r#"{"type": "algebraic", "notation": "e3h"}"#),
+            WireMove::Algebraic {
+                notation: "e3h".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn events() {
+        let start = round_trip::<WireEvent>(
+            r#"{"type": "game_start", "side": 0, "state": {"size": 9, "turn": 0,
+                "pawns": [{"id": 0, "owner": 0, "position": {"x": 4, "y": 0}},
+                          {"id": 1, "owner": 1, "position": {"x": 4, "y": 8}}],
+                "walls": [{"x": 5, "y": 3, "orientation": "vertical"}],
+                "walls_left": [9, 10]}}"#,
+        );
+        match start {
+            WireEvent::GameStart { side: 0, state } => {
+                assert_eq!((state.size, state.turn), (9, 0));
+                assert_eq!(state.pawns[1].position, WirePosition { x: 4, y: 8 });
+                assert_eq!(state.walls[0].orientation, WireOrientation::Vertical);
+                assert_eq!(state.walls_left, vec![9, 10]);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(
+            round_trip::<WireEvent>(
+                r#"{"type": "move", "player": 1, "notation": "e8",
+                    "move": {"type": "move_pawn", "from": {"x": 4, "y": 8}, "to": {"x": 4, "y": 7}}}"#
+            ),
+            WireEvent::Move {
+                player: 1,
+                qmove: WireMove::MovePawn {
+                    from: WirePosition { x: 4, y: 8 },
+                    to: WirePosition { x: 4, y: 7 },
+                },
+                notation: "e8".into(),
+            }
+        );
+        assert_eq!(
+            round_trip::<WireEvent>(r#"{"type": "game_end", "winner": 1}"#),
+            WireEvent::GameEnd { winner: Some(1) }
+        );
+        assert_eq!(
+            round_trip::<WireEvent>(r#"{"type": "game_end", "winner": null}"#),
+            WireEvent::GameEnd { winner: None }
+        );
+        assert_eq!(
+            round_trip::<WireEvent>(r#"{"type": "opponent_quit"}"#),
+            WireEvent::OpponentQuit
+        );
+        assert_eq!(
+            round_trip::<WireEvent>(r#"{"type": "error", "message": "invalid square 'z0'"}"#),
+            WireEvent::Error {
+                message: "invalid square 'z0'".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_messages() {
+        for json in &[
+            r#"{"type": "resign"}"#,
+            r#"{"type": "move_pawn", "from": {"x": 4, "y": 0}}"#,
+            r#"{"type": "place_wall", "wall": {"x": 5, "y": 3, "orientation": "diagonal"}}"#,
+            r#"{"notation": "e2"}"#,
+        ] {
+            assert!(serde_json::from_str::<WireMove>(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn walls_convert_both_ways() {
+        let wall = |orientation| Wall {
+            position: (5, 3).into(),
+            orientation,
+            wall_type: WallType::Simple,
+        };
+        for wall in &[wall(Orientation::Horizontal), wall(Orientation::Vertical)] {
+            assert_eq!(Wall::from(WireWall::from(wall)), *wall);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! A stand-in webhook target for trying webhooks out locally. It checks the
+//! signature of every delivery and prints it:
+//!
+//! ```text
+//! QWEB_WEBHOOK_SECRET=change-me cargo run --bin webhook_receiver -- --port 9000 --fail 2
+//! ```
+//!
+//! `--fail N` answers the first N deliveries with 503, to watch the server retry.
+
+use hmac::{Hmac, Mac, NewMac};
+use sha2::Sha256;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use warp::{http::StatusCode, hyper::body::Bytes, Filter};
+
+const DEFAULT_PORT: u16 = 9000;
+
+fn option<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let mut args = std::env::args().skip_while(|a| a != name).skip(1);
+    args.next().map(|v| {
+        v.parse()
+            .unwrap_or_else(|_| panic!("invalid value for {}: {}", name, v))
+    })
+}
+
+fn verify(secret: &str, body: &[u8], signature: &str) -> bool {
+    let signature = match signature
+        .strip_prefix("sha256=")
+        .and_then(|s| hex::decode(s).ok())
+    {
+        Some(signature) => signature,
+        None => return false,
+    };
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC takes any key");
+    mac.update(body);
+    mac.verify(&signature).is_ok()
+}
+
+#[tokio::main]
+async fn main() {
+    let port = option("--port").unwrap_or(DEFAULT_PORT);
+    let failures = Arc::new(AtomicUsize::new(option("--fail").unwrap_or(0)));
+    let secret = Arc::new(std::env::var("QWEB_WEBHOOK_SECRET").unwrap_or_default());
+
+    let receive = warp::post()
+        .and(warp::header::<String>("x-quoridor-event"))
+        .and(warp::header::<String>("x-quoridor-delivery"))
+        .and(warp::header::<String>("x-quoridor-signature"))
+        .and(warp::body::bytes())
+        .map(
+            move |event: String, delivery: String, signature: String, body: Bytes| {
+                if !verify(&secret, &body, &signature) {
+                    println!("{} {}: bad signature", delivery, event);
+                    return StatusCode::UNAUTHORIZED;
+                }
+                let failing = failures
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                    .is_ok();
+                if failing {
+                    println!("{} {}: failing on purpose", delivery, event);
+                    return StatusCode::SERVICE_UNAVAILABLE;
+                }
+                println!("{} {}: {}", delivery, event, String::from_utf8_lossy(&body));
+                StatusCode::NO_CONTENT
+            },
+        );
+
+    println!("Waiting for webhooks on http://127.0.0.1:{}/", port);
+    warp::serve(receive).run(([127, 0, 0, 1], port)).await;
+}
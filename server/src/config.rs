@@ -0,0 +1,99 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+
+const DEFAULT_CONFIG_PATH: &str = "./config.toml";
+
+/// Server configuration, read from `$QWEB_CONFIG` or `./config.toml`.
+#[derive(Deserialize, Default)]
+pub struct Config {
+    #[serde(default, rename = "engine")]
+    pub engines: Vec<EngineConfig>,
+    /// The puzzle file, `./puzzles.txt` if left out.
+    pub puzzles: Option<PathBuf>,
+    /// The file correspondence games are kept in, `./correspondence.json` if
+    /// left out.
+    pub correspondence: Option<PathBuf>,
+    #[serde(default, rename = "webhook")]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub log_format: LogFormat,
+}
+
+/// How log lines are written to stderr.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Readable lines, for a terminal.
+    Pretty,
+    /// One JSON object per line, for log collectors.
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Pretty
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct EngineConfig {
+    pub name: String,
+    pub command: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_movetime")]
+    pub movetime_ms: u64,
+}
+
+/// A target for the game events described in `webhook.rs`.
+#[derive(Deserialize, Clone)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// The key bodies are signed with.
+    pub secret: String,
+    /// The events to send, every one if left empty.
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+/// The events a webhook can ask for, named as in its configuration and the
+/// bodies it's sent.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    LobbyCreated,
+    GameStarted,
+    MovePlayed,
+    GameEnded,
+}
+
+impl WebhookEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::LobbyCreated => "lobby_created",
+            WebhookEvent::GameStarted => "game_started",
+            WebhookEvent::MovePlayed => "move_played",
+            WebhookEvent::GameEnded => "game_ended",
+        }
+    }
+}
+
+fn default_movetime() -> u64 {
+    1000
+}
+
+impl Config {
+    /// Loads the configuration, falling back to the defaults if there is no file.
+    pub fn load() -> Config {
+        let path = std::env::var("QWEB_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.into());
+        match std::fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text)
+                .unwrap_or_else(|e| panic!("invalid config file {}: {}", path, e)),
+            Err(_) => Config::default(),
+        }
+    }
+
+    pub fn engine(&self, name: &str) -> Option<&EngineConfig> {
+        self.engines.iter().find(|e| e.name == name)
+    }
+}
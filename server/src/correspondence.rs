@@ -0,0 +1,517 @@
+//! Correspondence games, see `common::correspondence`. They don't run on agents
+//! like live games but on a [`Board`], and are written to a file after every
+//! change so they outlast restarts.
+
+use super::*;
+use common::{
+    board::Board,
+    correspondence::{GameView, MoveRequest, Outcome, DAYS_PER_MOVE},
+    notation::{format_move, parse_board_move},
+    record::Termination,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use tokio::{sync::Mutex, time::delay_for};
+
+const DEFAULT_STORE_PATH: &str = "./correspondence.json";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// How often games are checked for players out of time.
+const DEADLINE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Deserialize, Default)]
+struct Player {
+    nickname: Option<String>,
+    /// Proves the seat is theirs, `None` while it's free.
+    token: Option<String>,
+}
+
+/// A game as it is kept in the file.
+#[derive(Serialize, Deserialize)]
+struct Record {
+    id: String,
+    size: u8,
+    days_per_move: u8,
+    players: Vec<Player>,
+    moves: Vec<String>,
+    /// When the player to move got the move, in seconds since the Unix epoch;
+    /// `None` until every seat is taken.
+    turn_started: Option<u64>,
+    outcome: Option<Outcome>,
+    created_at: u64,
+}
+
+struct Correspondence {
+    record: Record,
+    /// The position after `record.moves`.
+    board: Board,
+}
+
+/// The correspondence games, and the file they're kept in.
+#[derive(Clone)]
+pub struct Store {
+    games: Arc<RwLock<HashMap<String, Correspondence>>>,
+    path: Arc<PathBuf>,
+    /// Snapshots of the games taken for saving so far.
+    taken: Arc<AtomicU64>,
+    /// The last snapshot written. Writes can finish out of order, and an older
+    /// snapshot mustn't replace a newer one.
+    written: Arc<Mutex<u64>>,
+}
+
+impl Correspondence {
+    /// Plays a record's moves out, refusing it if any of them is illegal.
+    fn replay(record: Record) -> Result<Correspondence, String> {
+        let mut board = puzzle::start_position(record.size)
+            .ok_or_else(|| format!("unsupported board size {}", record.size))?;
+        for notation in &record.moves {
+            let qmv = parse_board_move(notation, &board)
+                .ok()
+                .filter(|qmv| board.is_legal(qmv))
+                .ok_or_else(|| format!("illegal move {} in game {}", notation, record.id))?;
+            board.apply(&qmv);
+        }
+        Ok(Correspondence { record, board })
+    }
+
+    fn side_of(&self, token: &str) -> Option<PlayerID> {
+        self.record
+            .players
+            .iter()
+            .position(|p| p.token.as_deref() == Some(token))
+            .map(|side| side as PlayerID)
+    }
+
+    fn deadline(&self) -> Option<u64> {
+        if self.record.outcome.is_some() {
+            return None;
+        }
+        let days = self.record.days_per_move as u64;
+        self.record
+            .turn_started
+            .map(|started| started + days * SECONDS_PER_DAY)
+    }
+
+    fn view(&self, token: Option<&str>) -> GameView {
+        let record = &self.record;
+        GameView {
+            id: record.id.clone(),
+            size: record.size,
+            players: record.players.iter().map(|p| p.nickname.clone()).collect(),
+            open: record.players.iter().any(|p| p.token.is_none()),
+            days_per_move: record.days_per_move,
+            moves: record.moves.clone(),
+            seat: token.and_then(|token| self.side_of(token)),
+            deadline: self.deadline(),
+            outcome: record.outcome.clone(),
+        }
+    }
+
+    /// The game as the lobby listings describe games, for the events bus.
+    fn info(&self) -> LobbyInfo {
+        let record = &self.record;
+        let settings = LobbySettings {
+            game_type: QGameType::StandardQuoridor,
+            size: record.size,
+            players: record.players.len(),
+        };
+        let taken = record.players.iter().filter(|p| p.token.is_some()).count();
+        LobbyInfo {
+            players: record.players.iter().map(|p| p.nickname.clone()).collect(),
+            created_at: record.created_at,
+            ..LobbyInfo::with_settings(&record.id, settings, taken)
+        }
+    }
+
+    /// Ends the game if the player to move ran out of time by `now`, returning
+    /// the winner.
+    fn expire(&mut self, now: u64) -> Option<Option<PlayerID>> {
+        if self.deadline()? >= now {
+            return None;
+        }
+        let winner = Some((self.board.turn + 1) % self.board.player_count());
+        self.record.outcome = Some(Outcome {
+            winner,
+            termination: Termination::Forfeit,
+        });
+        Some(winner)
+    }
+
+    /// Plays a move for `side`, if every seat is taken and it's their move and
+    /// a legal one.
+    fn play(&mut self, side: PlayerID, request: &MoveRequest, now: u64) -> Result<(), Rejection> {
+        let Correspondence { record, board } = self;
+        if record.players.iter().any(|p| p.token.is_none()) {
+            return Err(warp::reject::custom(WaitingForPlayers));
+        }
+        if record.outcome.is_some() || side != board.turn || request.ply != record.moves.len() {
+            return Err(warp::reject::custom(NotYourMove));
+        }
+        let qmv = parse_board_move(&request.notation, board)
+            .ok()
+            .filter(|qmv| board.is_legal(qmv))
+            .ok_or_else(|| warp::reject::custom(IllegalMove))?;
+        board.apply(&qmv);
+        record.moves.push(format_move(&qmv));
+        record.turn_started = Some(now);
+        if let Some(winner) = board.winner() {
+            record.outcome = Some(Outcome {
+                winner: Some(winner),
+                termination: Termination::Goal,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Store {
+    /// Writes every game to the file in the background, by way of a temporary
+    /// one so a crash can't leave it half written. Called with the games
+    /// locked, so snapshots are numbered in the order the games changed.
+    fn save(&self, games: &HashMap<String, Correspondence>) {
+        let mut records: Vec<_> = games.values().map(|g| &g.record).collect();
+        records.sort_by_key(|r| r.created_at);
+        let json = match serde_json::to_vec_pretty(&records) {
+            Ok(json) => json,
+            Err(e) => {
+                error!(error = %e, "correspondence games not saved");
+                return;
+            }
+        };
+        let snapshot = self.taken.fetch_add(1, Ordering::SeqCst) + 1;
+        let path = Arc::clone(&self.path);
+        let written = Arc::clone(&self.written);
+        tokio::spawn(async move {
+            let mut written = written.lock().await;
+            if *written > snapshot {
+                return;
+            }
+            let target = Arc::clone(&path);
+            let result = tokio::task::spawn_blocking(move || {
+                let temporary = target.with_extension("tmp");
+                std::fs::write(&temporary, json)?;
+                std::fs::rename(&temporary, &*target)
+            })
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result.map_err(|e| e.to_string()));
+            match result {
+                Ok(()) => *written = snapshot,
+                Err(e) => {
+                    error!(path = %path.display(), error = %e, "correspondence games not saved")
+                }
+            }
+        });
+    }
+}
+
+/// Loads the correspondence games, making sure every one can be played out.
+/// There are none if there is no file yet.
+pub fn load(path: Option<&Path>) -> Store {
+    let path = path.unwrap_or_else(|| Path::new(DEFAULT_STORE_PATH));
+    let mut games = HashMap::new();
+    if let Ok(text) = std::fs::read_to_string(path) {
+        let records: Vec<Record> = serde_json::from_str(&text).unwrap_or_else(|e| {
+            panic!(
+                "invalid correspondence games file {}: {}",
+                path.display(),
+                e
+            )
+        });
+        for record in records {
+            let game = Correspondence::replay(record).unwrap_or_else(|e| {
+                panic!(
+                    "invalid correspondence games file {}: {}",
+                    path.display(),
+                    e
+                )
+            });
+            games.insert(game.record.id.clone(), game);
+        }
+    }
+    Store {
+        games: Arc::new(RwLock::new(games)),
+        path: Arc::new(path.to_path_buf()),
+        taken: Arc::default(),
+        written: Arc::new(Mutex::new(0)),
+    }
+}
+
+/// Random hex digits from the operating system, for ids and tokens that can't
+/// be guessed.
+fn random_hex(digits: usize) -> String {
+    let mut bytes = vec![0; (digits + 1) / 2];
+    getrandom::getrandom(&mut bytes).expect("no source of randomness");
+    let mut hex = hex::encode(bytes);
+    hex.truncate(digits);
+    hex
+}
+
+/// Ends the games whose player to move ran out of time, every minute or so.
+pub async fn enforce_deadlines(store: Store, events: Events) {
+    loop {
+        delay_for(DEADLINE_CHECK_INTERVAL).await;
+        let now = unix_time();
+        let mut games = store.games.write().await;
+        let expired: Vec<_> = games
+            .values_mut()
+            .filter_map(|game| Some((game.record.id.clone(), game.expire(now)?)))
+            .collect();
+        if expired.is_empty() {
+            continue;
+        }
+        store.save(&games);
+        for (name, winner) in expired {
+            info!(game = %name, "correspondence move out of time");
+            announce(
+                &events,
+                ServerEvent::GameEnded {
+                    name,
+                    winner,
+                    termination: Termination::Forfeit,
+                },
+            );
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CorrespondenceRequest {
+    #[serde(default = "default_size")]
+    size: u8,
+    days_per_move: u8,
+    #[serde(default)]
+    side: SideChoice,
+    #[serde(default)]
+    nick: String,
+}
+
+#[derive(Deserialize)]
+struct JoinRequest {
+    #[serde(default)]
+    nick: String,
+}
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+/// Sends a player to their seat in the game page, the token in the link.
+fn to_seat(id: &str, token: &str) -> impl warp::Reply {
+    let path = format!(
+        "/game/standard/correspondence?mode=correspondence&id={}&token={}",
+        id, token
+    );
+    warp::redirect(Uri::builder().path_and_query(&path[..]).build().unwrap())
+}
+
+/// The correspondence routes, all under `/correspondence`:
+///
+/// - `POST new` creates a game from a form, `POST {id}/join` takes its free
+///   seat; both send the player on to the game page with their token.
+/// - `GET open` lists the games with a free seat.
+/// - `GET {id}?token=…` shows a game, as the token's side if it holds one.
+/// - `POST {id}/move` plays a `MoveRequest`, answering with the game.
+/// - `POST mine` shows the games of a list of tokens, for the dashboard.
+pub fn routes(
+    store: Store,
+    events: Events,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
+    let new = warp::post()
+        .and(path!("correspondence" / "new"))
+        .and(warp::body::form())
+        .and(warpify!(store))
+        .and_then(|request: CorrespondenceRequest, store: Store| async move {
+            if !BOARD_SIZES.contains(&request.size) {
+                return Err(warp::reject::custom(UnsupportedBoardSize));
+            }
+            if !DAYS_PER_MOVE.contains(&request.days_per_move) {
+                return Err(warp::reject::custom(UnsupportedDaysPerMove));
+            }
+            let nickname = parse_nickname(&request.nick)?;
+            let board = puzzle::start_position(request.size)
+                .ok_or_else(|| warp::reject::custom(UnsupportedBoardSize))?;
+
+            let mut games = store.games.write().await;
+            let id = std::iter::repeat_with(|| random_hex(10))
+                .find(|id| !games.contains_key(id))
+                .unwrap();
+            let token = random_hex(32);
+            let mut players: Vec<Player> = (0..board.player_count())
+                .map(|_| Player::default())
+                .collect();
+            players[request.side.pick() as usize] = Player {
+                nickname,
+                token: Some(token.clone()),
+            };
+            let record = Record {
+                id: id.clone(),
+                size: request.size,
+                days_per_move: request.days_per_move,
+                players,
+                moves: vec![],
+                turn_started: None,
+                outcome: None,
+                created_at: unix_time(),
+            };
+            games.insert(id.clone(), Correspondence { record, board });
+            store.save(&games);
+            info!(
+                game = %id,
+                size = request.size,
+                days_per_move = request.days_per_move,
+                "correspondence game created"
+            );
+            Ok::<_, Rejection>(to_seat(&id, &token))
+        });
+
+    let join = warp::post()
+        .and(path!("correspondence" / String / "join"))
+        .and(warp::body::form())
+        .and(warpify!(store))
+        .and(warpify!(events))
+        .and_then(
+            |id: String, request: JoinRequest, store: Store, events: Events| async move {
+                let nickname = parse_nickname(&request.nick)?;
+                let mut games = store.games.write().await;
+                let game = games.get_mut(&id).ok_or_else(warp::reject::not_found)?;
+                let player = game
+                    .record
+                    .players
+                    .iter_mut()
+                    .find(|p| p.token.is_none())
+                    .ok_or_else(|| warp::reject::custom(GameFull))?;
+                let token = random_hex(32);
+                *player = Player {
+                    nickname,
+                    token: Some(token.clone()),
+                };
+                info!(game = %id, "correspondence seat taken");
+                // The clock starts once everyone is seated.
+                let started = game.record.players.iter().all(|p| p.token.is_some());
+                if started {
+                    game.record.turn_started = Some(unix_time());
+                    announce(&events, ServerEvent::GameStarted(game.info()));
+                }
+                store.save(&games);
+                Ok::<_, Rejection>(to_seat(&id, &token))
+            },
+        );
+
+    let open = warp::get()
+        .and(path!("correspondence" / "open"))
+        .and(warpify!(store))
+        .and_then(|store: Store| async move {
+            let games = store.games.read().await;
+            let mut open: Vec<_> = games
+                .values()
+                .filter(|g| g.record.players.iter().any(|p| p.token.is_none()))
+                .collect();
+            open.sort_by_key(|g| g.record.created_at);
+            let views: Vec<_> = open.into_iter().map(|g| g.view(None)).collect();
+            Ok::<_, Rejection>(warp::reply::json(&views))
+        });
+
+    let mine = warp::post()
+        .and(path!("correspondence" / "mine"))
+        .and(warp::body::json())
+        .and(warpify!(store))
+        .and_then(|tokens: Vec<String>, store: Store| async move {
+            let games = store.games.read().await;
+            let views: Vec<_> = tokens
+                .iter()
+                .filter_map(|token| {
+                    let game = games.values().find(|g| g.side_of(token).is_some())?;
+                    Some(game.view(Some(token)))
+                })
+                .collect();
+            Ok::<_, Rejection>(warp::reply::json(&views))
+        });
+
+    let get = warp::get()
+        .and(path!("correspondence" / String))
+        .and(warp::query::<TokenQuery>())
+        .and(warpify!(store))
+        .and_then(|id: String, query: TokenQuery, store: Store| async move {
+            let games = store.games.read().await;
+            let game = games.get(&id).ok_or_else(warp::reject::not_found)?;
+            Ok::<_, Rejection>(warp::reply::json(&game.view(query.token.as_deref())))
+        });
+
+    let play = warp::post()
+        .and(path!("correspondence" / String / "move"))
+        .and(warp::body::json())
+        .and(warpify!(store))
+        .and(warpify!(events))
+        .and_then(
+            |id: String, request: MoveRequest, store: Store, events: Events| async move {
+                let mut games = store.games.write().await;
+                let game = games.get_mut(&id).ok_or_else(warp::reject::not_found)?;
+                let side = game
+                    .side_of(&request.token)
+                    .ok_or_else(|| warp::reject::custom(InvalidToken))?;
+                let now = unix_time();
+                // Too late is too late, even if the deadlines weren't checked yet.
+                let played = match game.expire(now) {
+                    Some(_) => None,
+                    None => {
+                        game.play(side, &request, now)?;
+                        game.record.moves.last().cloned()
+                    }
+                };
+                let ended = game.record.outcome.clone();
+                let view = game.view(Some(&request.token));
+                store.save(&games);
+                if let Some(notation) = played {
+                    announce(
+                        &events,
+                        ServerEvent::MovePlayed {
+                            name: id.clone(),
+                            player: side,
+                            notation,
+                        },
+                    );
+                }
+                if let Some(outcome) = ended {
+                    announce(
+                        &events,
+                        ServerEvent::GameEnded {
+                            name: id,
+                            winner: outcome.winner,
+                            termination: outcome.termination,
+                        },
+                    );
+                }
+                Ok::<_, Rejection>(warp::reply::json(&view))
+            },
+        );
+
+    new.or(join).or(open).or(mine).or(get).or(play)
+}
+
+#[derive(Debug)]
+struct UnsupportedDaysPerMove;
+impl warp::reject::Reject for UnsupportedDaysPerMove {}
+
+#[derive(Debug)]
+struct GameFull;
+impl warp::reject::Reject for GameFull {}
+
+#[derive(Debug)]
+struct WaitingForPlayers;
+impl warp::reject::Reject for WaitingForPlayers {}
+
+#[derive(Debug)]
+struct InvalidToken;
+impl warp::reject::Reject for InvalidToken {}
+
+#[derive(Debug)]
+struct NotYourMove;
+impl warp::reject::Reject for NotYourMove {}
+
+#[derive(Debug)]
+struct IllegalMove;
+impl warp::reject::Reject for IllegalMove {}
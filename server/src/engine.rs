@@ -0,0 +1,237 @@
+use super::*;
+use crate::config::EngineConfig;
+use common::{
+    engine::{EngineCommand, EngineReply},
+    notation::{format_move, parse_move},
+};
+use std::{process::Stdio, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout, Command},
+    time::{delay_for, timeout},
+};
+
+/// How long an engine gets to answer `uqi` and `isready`.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Extra time granted on top of `movetime` before the engine is told to stop, and again before it forfeits.
+const GRACE_PERIOD: Duration = Duration::from_millis(500);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+type EngineError = Box<dyn Error + Send + Sync>;
+
+fn matching(reply: EngineReply, expected: EngineReply) -> Option<()> {
+    if reply == expected {
+        Some(())
+    } else {
+        None
+    }
+}
+
+struct EngineProcess {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl EngineProcess {
+    fn spawn(config: &EngineConfig) -> Result<Self, EngineError> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(EngineProcess {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+
+    async fn send(&mut self, command: EngineCommand) -> Result<(), EngineError> {
+        self.stdin
+            .write_all(format!("{}\n", command).as_bytes())
+            .await?;
+        Ok(())
+    }
+
+    /// Reads lines until one matches `want`, giving up after `limit`.
+    async fn wait_for<T>(
+        &mut self,
+        limit: Duration,
+        mut want: impl FnMut(EngineReply) -> Option<T>,
+    ) -> Result<Option<T>, EngineError> {
+        let stdout = &mut self.stdout;
+        let read = async {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                if stdout.read_line(&mut line).await? == 0 {
+                    return Err::<_, EngineError>("engine closed its output".into());
+                }
+                if let Some(found) = EngineReply::parse(&line).and_then(&mut want) {
+                    return Ok(found);
+                }
+            }
+        };
+        match timeout(limit, read).await {
+            Ok(result) => result.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    async fn handshake(&mut self) -> Result<(), EngineError> {
+        self.send(EngineCommand::Uqi).await?;
+        self.wait_for(HANDSHAKE_TIMEOUT, |r| matching(r, EngineReply::UqiOk))
+            .await?
+            .ok_or("engine didn't answer uqi")?;
+        self.send(EngineCommand::IsReady).await?;
+        self.wait_for(HANDSHAKE_TIMEOUT, |r| matching(r, EngineReply::ReadyOk))
+            .await?
+            .ok_or("engine didn't answer isready")?;
+        Ok(())
+    }
+
+    /// Asks for a move, returning `None` if the engine overstepped its time.
+    async fn think(
+        &mut self,
+        moves: &[String],
+        movetime: u64,
+    ) -> Result<Option<String>, EngineError> {
+        fn best(reply: EngineReply) -> Option<String> {
+            match reply {
+                EngineReply::BestMove(qmv) => Some(qmv),
+                _ => None,
+            }
+        }
+
+        self.send(EngineCommand::Position {
+            moves: moves.to_vec(),
+        })
+        .await?;
+        self.send(EngineCommand::Go { movetime }).await?;
+
+        let limit = Duration::from_millis(movetime) + GRACE_PERIOD;
+        if let Some(qmv) = self.wait_for(limit, best).await? {
+            return Ok(Some(qmv));
+        }
+        self.send(EngineCommand::Stop).await?;
+        self.wait_for(GRACE_PERIOD, best).await
+    }
+
+    /// Tells the engine to quit, killing it if it hasn't within the grace period.
+    async fn quit(mut self) {
+        let _ = self.send(EngineCommand::Quit).await;
+        if timeout(GRACE_PERIOD, &mut self.child).await.is_err() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+/// Seats a local engine executable in a lobby, in place of a websocket client.
+pub fn host(agent: QAgent, config: EngineConfig, hub: Hub, name: String, settings: LobbySettings) {
+    let span = info_span!("engine", game = %name, engine = %config.name);
+    tokio::spawn(
+        async move {
+            info!("engine seated");
+            let mut seat = None;
+            if let Err(e) = play(&agent, &config, &hub, &name, settings, &mut seat).await {
+                warn!(error = %e, "engine left");
+                // Once the game is on, an engine that fails loses it.
+                match seat {
+                    Some(side) => {
+                        forfeit(&hub, &name, side, settings, &e.to_string()).await;
+                        rematch::leave(&hub, &name, side, settings).await;
+                    }
+                    None => abandon_game(&hub, &name).await,
+                }
+            }
+        }
+        .instrument(span),
+    );
+}
+
+async fn play(
+    agent: &QAgent,
+    config: &EngineConfig,
+    hub: &Hub,
+    name: &str,
+    settings: LobbySettings,
+    seat: &mut Option<PlayerID>,
+) -> Result<(), EngineError> {
+    let mut engine = EngineProcess::spawn(config)?;
+    engine.handshake().await?;
+
+    let mut state: Option<(Quoridor, PlayerID)> = None;
+    let mut moves = vec![];
+
+    loop {
+        let event = match agent.recv_event() {
+            Ok(event) => event,
+            // The game was dropped without a word to the agent.
+            Err(e) if e.is_disconnected() => break,
+            Err(_) => {
+                delay_for(POLL_INTERVAL).await;
+                continue;
+            }
+        };
+
+        match event {
+            QGameEvent::GameStart(game, side) => {
+                engine
+                    .send(EngineCommand::NewGame { size: game.size() })
+                    .await?;
+                moves.clear();
+                *seat = Some(side);
+                state = Some((game, side));
+            }
+            QGameEvent::MoveHappened(qmv) => {
+                if let Some((game, _)) = &mut state {
+                    moves.push(format_move(&qmv));
+                    game.apply_move(&qmv);
+                }
+            }
+            QGameEvent::GameEnd(winner) => {
+                *seat = None;
+                end_game(&hub.games, &hub.events, name, winner, Termination::Goal).await;
+                // Engines don't take rematches, so let the other player know.
+                if let Some((_, side)) = &state {
+                    rematch::leave(hub, name, *side, settings).await;
+                }
+                break;
+            }
+            QGameEvent::OpponentQuit => {
+                *seat = None;
+                break;
+            }
+            _ => continue,
+        }
+
+        if let Some((game, side)) = &state {
+            if game.turn_of() == *side {
+                let notation = engine
+                    .think(&moves, config.movetime_ms)
+                    .await?
+                    .ok_or("ran out of time")?;
+                let received = Instant::now();
+                let qmv = parse_move(&notation, game)
+                    .map_err(|e| format!("sent an invalid move: {}", e))?;
+                if !is_legal(settings.game_type, game, *side, &qmv) {
+                    return Err(format!("sent an illegal move: {}", notation).into());
+                }
+                agent
+                    .send_move(RulebookMove::wrap(game, &qmv))
+                    .map_err(|_| "the game is over")?;
+                step_game(hub, name, *side, format_move(&qmv), received).await?;
+            }
+        }
+    }
+    // Engines don't take rematches after forfeits either.
+    if let Some(side) = seat.take() {
+        rematch::leave(hub, name, side, settings).await;
+    }
+    engine.quit().await;
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use super::*;
+use tokio::sync::broadcast;
+
+/// Lifecycle notifications other parts of the server can subscribe to.
+#[derive(Debug, Clone)]
+pub enum ServerEvent {
+    GameEnded {
+        name: String,
+        winner: Option<PlayerID>,
+        /// `Forfeit` for games the server ended rather than the rulebook, which
+        /// the hosts pass on since their agents never hear of it.
+        termination: Termination,
+    },
+    LobbyCreated(LobbyInfo),
+    /// A seat was taken; the lobby is `Playing` once they all are.
+    LobbyUpdated(LobbyInfo),
+    /// Every seat is taken and the first move is due.
+    GameStarted(LobbyInfo),
+    MovePlayed {
+        name: String,
+        player: PlayerID,
+        notation: String,
+    },
+    /// The lobby or its game was abandoned.
+    LobbyRemoved {
+        name: String,
+    },
+}
+
+/// Where events are announced: the bus anyone can subscribe to, and what
+/// mustn't miss any. A subscriber that lags behind the bus does, so the
+/// webhooks and tournaments get queues of their own and the metrics are
+/// counted on the spot.
+#[derive(Clone)]
+pub struct Events {
+    bus: broadcast::Sender<ServerEvent>,
+    queues: Vec<mpsc::UnboundedSender<ServerEvent>>,
+    metrics: Arc<Metrics>,
+}
+
+impl Events {
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.bus.subscribe()
+    }
+
+    /// Queues every event from now on for a subscriber that mustn't miss any.
+    /// Only this and later clones announce to the queue.
+    pub fn queue(&mut self) -> mpsc::UnboundedReceiver<ServerEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.queues.push(sender);
+        receiver
+    }
+}
+
+pub fn channel() -> Events {
+    Events {
+        bus: broadcast::channel(64).0,
+        queues: Vec::new(),
+        metrics: Arc::default(),
+    }
+}
+
+/// Logs and counts `event` as the lifecycle transition it is, then sends it. The log line
+/// falls in the span of whatever caused it, such as the connection of the
+/// player who moved.
+pub fn announce(events: &Events, event: ServerEvent) {
+    match &event {
+        ServerEvent::LobbyCreated(info) => info!(
+            lobby = %info.name,
+            game_type = info.game_type,
+            size = info.size,
+            seats = info.seats,
+            "lobby created"
+        ),
+        ServerEvent::LobbyUpdated(info) => info!(
+            lobby = %info.name,
+            taken = info.taken,
+            seats = info.seats,
+            players = ?info.players,
+            "seat taken"
+        ),
+        ServerEvent::GameStarted(info) => {
+            info!(game = %info.name, players = ?info.players, "game started")
+        }
+        ServerEvent::MovePlayed {
+            name,
+            player,
+            notation,
+        } => info!(game = %name, side = player, notation = %notation, "move played"),
+        ServerEvent::GameEnded {
+            name,
+            winner,
+            termination,
+        } => {
+            let result = if winner.is_some() { "won" } else { "drawn" };
+            info!(game = %name, winner = ?winner, result, termination = ?termination, "game ended")
+        }
+        ServerEvent::LobbyRemoved { name } => info!(lobby = %name, "lobby removed"),
+    }
+    events.metrics.count(&event);
+    for queue in &events.queues {
+        let _ = queue.send(event.clone());
+    }
+    let _ = events.bus.send(event);
+}
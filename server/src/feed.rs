@@ -0,0 +1,292 @@
+use super::*;
+use futures::{future, stream};
+use std::collections::HashSet;
+use tokio::sync::broadcast;
+
+/// Lobbies and the games they turned into, as the lobby page lists them.
+pub type Listings = Arc<RwLock<HashMap<String, LobbyInfo>>>;
+
+/// How many finished games stay listed, the oldest going first.
+const FINISHED_KEPT: usize = 100;
+const DEFAULT_PAGE: usize = 50;
+const MAX_PAGE: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LobbyStatus {
+    /// Some seats are still free.
+    Waiting,
+    InProgress,
+    Finished,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LobbyInfo {
+    pub name: String,
+    pub game_type: &'static str,
+    pub size: u8,
+    pub seats: usize,
+    pub taken: usize,
+    pub status: LobbyStatus,
+    /// Nicknames by side, `None` for free seats and anonymous players.
+    pub players: Vec<Option<String>>,
+    pub creator: Option<String>,
+    pub time_control: Option<String>,
+    pub private: bool,
+    /// Seconds since the Unix epoch.
+    pub created_at: u64,
+}
+
+impl LobbyInfo {
+    pub fn new(name: &str, lobby: &Lobby) -> Self {
+        let taken = lobby.agents.iter().filter(|a| a.is_none()).count();
+        LobbyInfo {
+            players: lobby.players.clone(),
+            creator: lobby.details.creator.clone(),
+            time_control: lobby.details.time_control.clone(),
+            private: lobby.details.private,
+            created_at: lobby.created_at,
+            ..Self::with_settings(name, lobby.settings(), taken)
+        }
+    }
+
+    /// The listing of a game without a lobby of its own, such as a rematch.
+    pub fn with_settings(name: &str, settings: LobbySettings, taken: usize) -> Self {
+        LobbyInfo {
+            name: name.into(),
+            game_type: gtstr(&settings.game_type),
+            size: settings.size,
+            seats: settings.players,
+            taken,
+            status: if taken < settings.players {
+                LobbyStatus::Waiting
+            } else {
+                LobbyStatus::InProgress
+            },
+            players: vec![None; settings.players],
+            creator: None,
+            time_control: None,
+            private: false,
+            created_at: unix_time(),
+        }
+    }
+}
+
+/// Forgets the oldest finished games beyond `FINISHED_KEPT`.
+fn prune(listings: &mut HashMap<String, LobbyInfo>) {
+    let mut finished: Vec<_> = listings
+        .values()
+        .filter(|l| l.status == LobbyStatus::Finished)
+        .map(|l| (l.created_at, l.name.clone()))
+        .collect();
+    if finished.len() > FINISHED_KEPT {
+        finished.sort();
+        for (_, name) in &finished[..finished.len() - FINISHED_KEPT] {
+            listings.remove(name);
+        }
+    }
+}
+
+/// The listings a page is sent when it starts following the lobbies, or has
+/// missed events since.
+fn snapshot(listings: &HashMap<String, LobbyInfo>) -> Vec<LobbyInfo> {
+    listings
+        .values()
+        .filter(|l| l.status != LobbyStatus::Finished)
+        .cloned()
+        .collect()
+}
+
+/// Rebuilds the listings of open lobbies and running games from the hub, after
+/// missing events about them. Games that ended meanwhile can't be told from
+/// abandoned ones, so both drop out.
+async fn resync(listings: &Listings, hub: &Hub) {
+    let lobbies = hub.lobbies.read().await;
+    let games = hub.games.read().await;
+    let mut listings = listings.write().await;
+    listings.retain(|name, l| {
+        l.status == LobbyStatus::Finished || lobbies.contains_key(name) || games.contains_key(name)
+    });
+    for (name, lobby) in lobbies.iter() {
+        listings.insert(name.clone(), LobbyInfo::new(name, lobby));
+    }
+}
+
+/// Keeps `listings` in step with the lobby events, so new subscribers can be
+/// sent what they missed.
+pub async fn track(listings: Listings, hub: Hub, mut events: broadcast::Receiver<ServerEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let mut listings = listings.write().await;
+                match event {
+                    ServerEvent::LobbyCreated(info) | ServerEvent::LobbyUpdated(info) => {
+                        listings.insert(info.name.clone(), info);
+                    }
+                    ServerEvent::LobbyRemoved { name } => {
+                        listings.remove(&name);
+                    }
+                    ServerEvent::GameEnded { name, .. } => {
+                        if let Some(listing) = listings.get_mut(&name) {
+                            listing.status = LobbyStatus::Finished;
+                        }
+                        prune(&mut listings);
+                    }
+                    ServerEvent::GameStarted(_) | ServerEvent::MovePlayed { .. } => {}
+                }
+            }
+            Err(broadcast::RecvError::Lagged(_)) => resync(&listings, &hub).await,
+            Err(broadcast::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// What a page following the lobbies is sent.
+enum Update {
+    Event(ServerEvent),
+    /// Every listing, replacing those the page has, after it missed events.
+    Snapshot(Vec<LobbyInfo>),
+}
+
+/// The server-sent event for an update, unless it's about a private lobby.
+/// `shown` holds the lobbies the page has heard of, the only ones it hears of
+/// being removed, so private lobbies' names don't get out that way either.
+fn sse_event(
+    update: Update,
+    shown: &mut HashSet<String>,
+) -> Option<impl warp::sse::ServerSentEvent> {
+    let event = match update {
+        Update::Event(event) => event,
+        Update::Snapshot(listings) => {
+            let public: Vec<_> = listings.into_iter().filter(|l| !l.private).collect();
+            *shown = public.iter().map(|l| l.name.clone()).collect();
+            let data = serde_json::to_value(public).unwrap();
+            return Some((warp::sse::event("snapshot"), warp::sse::json(data)));
+        }
+    };
+    let (kind, data) = match event {
+        ServerEvent::LobbyCreated(info) | ServerEvent::LobbyUpdated(info) if info.private => {
+            return None
+        }
+        ServerEvent::LobbyCreated(info) => {
+            shown.insert(info.name.clone());
+            ("created", serde_json::to_value(info).unwrap())
+        }
+        ServerEvent::LobbyUpdated(info) => {
+            shown.insert(info.name.clone());
+            ("updated", serde_json::to_value(info).unwrap())
+        }
+        ServerEvent::LobbyRemoved { name } | ServerEvent::GameEnded { name, .. } => {
+            if !shown.remove(&name) {
+                return None;
+            }
+            ("removed", serde_json::json!({ "name": name }))
+        }
+        // Listings already turn `in_progress` with the last `updated`.
+        ServerEvent::GameStarted(_) | ServerEvent::MovePlayed { .. } => return None,
+    };
+    Some((warp::sse::event(kind), warp::sse::json(data)))
+}
+
+/// `GET /lobby/events`: every current listing as a `created` event, then
+/// `created`, `updated` and `removed` events as lobbies change. A `snapshot`
+/// of every listing replaces them all if the feed falls behind.
+pub fn route(
+    listings: Listings,
+    events: Events,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(path!("lobby" / "events"))
+        .and(warpify!(listings))
+        .and(warpify!(events))
+        .and_then(|listings: Listings, events: Events| async move {
+            // Subscribe first so nothing falls between the snapshot and the feed;
+            // the page treats a repeated `created` as an update.
+            let rx = events.subscribe();
+            let created: Vec<_> = snapshot(&*listings.read().await)
+                .into_iter()
+                .map(|l| Update::Event(ServerEvent::LobbyCreated(l)))
+                .collect();
+            let live = stream::unfold((rx, listings), |(mut rx, listings)| async move {
+                let update = match rx.recv().await {
+                    Ok(event) => Update::Event(event),
+                    Err(broadcast::RecvError::Lagged(_)) => {
+                        Update::Snapshot(snapshot(&*listings.read().await))
+                    }
+                    Err(broadcast::RecvError::Closed) => return None,
+                };
+                Some((update, (rx, listings)))
+            });
+            let mut shown = HashSet::new();
+            let feed = stream::iter(created).chain(live).filter_map(move |update| {
+                future::ready(sse_event(update, &mut shown).map(Ok::<_, Infallible>))
+            });
+            Ok::<_, Infallible>(warp::sse::reply(warp::sse::keep_alive().stream(feed)))
+        })
+}
+
+#[derive(Deserialize)]
+struct ListQuery {
+    game_type: Option<String>,
+    status: Option<LobbyStatus>,
+    #[serde(default)]
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl ListQuery {
+    /// Private lobbies are joined by name only, so they never match.
+    fn matches(&self, listing: &LobbyInfo) -> bool {
+        !listing.private
+            && self
+                .game_type
+                .as_deref()
+                .map_or(true, |gt| gt == listing.game_type)
+            && self.status.map_or(true, |s| s == listing.status)
+    }
+}
+
+/// `GET /lobby/list`: the public listings matching the query, oldest first, a
+/// page of them at a time. `X-Total-Count` tells how many matched in all.
+pub fn list(
+    listings: Listings,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(path!("lobby" / "list"))
+        .and(warp::query::<ListQuery>())
+        .and(warpify!(listings))
+        .and_then(|query: ListQuery, listings: Listings| async move {
+            let listings = listings.read().await;
+            let mut matching: Vec<_> = listings.values().filter(|l| query.matches(l)).collect();
+            matching.sort_by(|a, b| (a.created_at, &a.name).cmp(&(b.created_at, &b.name)));
+
+            let total = matching.len();
+            let limit = query.limit.unwrap_or(DEFAULT_PAGE).min(MAX_PAGE);
+            let page: Vec<_> = matching
+                .into_iter()
+                .skip(query.offset)
+                .take(limit)
+                .collect();
+            Ok::<_, Infallible>(warp::reply::with_header(
+                warp::reply::json(&page),
+                "X-Total-Count",
+                total.to_string(),
+            ))
+        })
+}
+
+/// `GET /lobby/info/<name>`: the listing of one lobby, private or not, for
+/// those who know its name.
+pub fn info(
+    listings: Listings,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(path!("lobby" / "info" / String))
+        .and(warpify!(listings))
+        .and_then(|name: String, listings: Listings| async move {
+            match listings.read().await.get(&name) {
+                Some(listing) => Ok(warp::reply::json(listing)),
+                None => Err(warp::reject::not_found()),
+            }
+        })
+}
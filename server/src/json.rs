@@ -0,0 +1,200 @@
+use super::*;
+use common::{
+    notation::format_move,
+    wire::{WireEvent, WireMove, WireState},
+};
+use std::sync::Mutex;
+
+fn text(event: &WireEvent) -> Result<Message, warp::Error> {
+    Ok(Message::text(serde_json::to_string(event).unwrap()))
+}
+
+/// Hosts an agent over the JSON protocol described in `common::wire`.
+///
+/// Unlike the bincode host this works on the unified `QAgent`, keeping a copy of
+/// the game so JSON moves can be wrapped for the rulebook and events described.
+pub fn host(agent: QAgent, socket: WebSocket, hub: Hub, name: String, seat: Seat) {
+    let (wstx, mut wsrx) = socket.split();
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(rx.forward(wstx));
+    let Seat {
+        side,
+        settings,
+        players,
+        events: mut seat_events,
+    } = seat;
+    let nickname = players[side as usize].clone();
+    send_control(&tx, &ServerControl::Seated { side });
+    send_control(&tx, &ServerControl::Players { names: players });
+
+    let (next_tx, mut next_rx) = mpsc::unbounded_channel();
+    // Swapped out, along with the name, when a rematch starts.
+    let current = Arc::new(Mutex::new(Current {
+        agent: Arc::new(agent),
+        name,
+        side,
+        finished: false,
+    }));
+    let game: Arc<Mutex<Option<Quoridor>>> = Default::default();
+
+    let error_tx = tx.clone();
+    let (in_hub, in_current, move_game) = (hub.clone(), current.clone(), game.clone());
+    tokio::spawn(
+        async move {
+            let (hub, current) = (in_hub, in_current);
+            let _connection = hub.metrics.connect();
+            while let Some(result) = wsrx.next().await {
+                let msg = match result {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        warn!(error = %e, "connection failed");
+                        break;
+                    }
+                };
+                let received = Instant::now();
+                let (agent, name, side, finished) = {
+                    let c = current.lock().unwrap();
+                    (c.agent.clone(), c.name.clone(), c.side, c.finished)
+                };
+                if msg.is_close() {
+                    info!(game = %name, "player quit");
+                    if finished {
+                        rematch::leave(&hub, &name, side, settings).await;
+                    }
+                    abandon_game(&hub, &name).await;
+                    break;
+                }
+
+                // Control messages share text frames with moves, see `common::control`.
+                match serde_json::from_str(msg.to_str().unwrap_or("")) {
+                    Ok(ClientControl::RematchOffer) if finished => {
+                        rematch::offer(&hub, &name, side).await;
+                        continue;
+                    }
+                    Ok(ClientControl::RematchDecline) if finished => {
+                        rematch::leave(&hub, &name, side, settings).await;
+                        continue;
+                    }
+                    Ok(_) => continue,
+                    Err(_) => {}
+                }
+
+                let sent = match msg.to_str().map(serde_json::from_str::<WireMove>) {
+                    Ok(Ok(wmv)) => match &*move_game.lock().unwrap() {
+                        Some(game) => {
+                            wmv.into_move(game)
+                                .map_err(|e| e.to_string())
+                                .and_then(|qmv| {
+                                    // Checked first, so the notation is only written
+                                    // for moves on the board.
+                                    if !is_legal(settings.game_type, game, side, &qmv) {
+                                        return Err(String::from("illegal move"));
+                                    }
+                                    agent
+                                        .send_move(RulebookMove::wrap(game, &qmv))
+                                        .map_err(|_| String::from("the game is over"))?;
+                                    Ok(format_move(&qmv))
+                                })
+                        }
+                        None => Err("the game hasn't started yet".into()),
+                    },
+                    Ok(Err(e)) => {
+                        hub.metrics.json_decode_failures.inc();
+                        Err(e.to_string())
+                    }
+                    Err(_) => Err("expected a text message".into()),
+                };
+
+                let played = match sent {
+                    Ok(notation) => step_game(&hub, &name, side, notation, received).await,
+                    Err(message) => Err(message),
+                };
+                if let Err(message) = played {
+                    warn!(game = %name, error = %message, "move refused");
+                    let _ = error_tx.send(text(&WireEvent::Error { message }));
+                }
+            }
+            info!("disconnected");
+        }
+        .in_current_span(),
+    );
+
+    tokio::spawn(
+        async move {
+            loop {
+                let agent = current.lock().unwrap().agent.clone();
+                if let Ok(event) = agent.recv_event() {
+                    let event = match event {
+                        QGameEvent::GameStart(g, side) => {
+                            let event = WireEvent::GameStart {
+                                side,
+                                state: WireState::from(&g),
+                            };
+                            *game.lock().unwrap() = Some(g);
+                            current.lock().unwrap().side = side;
+                            event
+                        }
+                        QGameEvent::MoveHappened(qmv) => {
+                            let mut game = game.lock().unwrap();
+                            match game.as_mut() {
+                                Some(g) => {
+                                    let player = g.turn_of();
+                                    g.apply_move(&qmv);
+                                    WireEvent::Move {
+                                        player,
+                                        qmove: WireMove::from(&qmv),
+                                        notation: format_move(&qmv),
+                                    }
+                                }
+                                None => continue,
+                            }
+                        }
+                        QGameEvent::GameEnd(winner) => {
+                            let (name, side) = {
+                                let mut c = current.lock().unwrap();
+                                c.finished = true;
+                                (c.name.clone(), c.side)
+                            };
+                            end_game(&hub.games, &hub.events, &name, winner, Termination::Goal)
+                                .await;
+                            // Nobody is listening once the connection has closed.
+                            if tx.send(text(&WireEvent::GameEnd { winner })).is_err() {
+                                break;
+                            }
+                            // After the game_end, which any rematch messages follow.
+                            let participant =
+                                rematch::Participant::new(tx.clone(), next_tx.clone(), &nickname);
+                            rematch::join(&hub, &name, side, settings, participant).await;
+                            continue;
+                        }
+                        QGameEvent::OpponentQuit => WireEvent::OpponentQuit,
+                        _ => continue,
+                    };
+                    if tx.send(text(&event)).is_err() {
+                        break;
+                    }
+                }
+                if let Ok((agent, name)) = next_rx.try_recv() {
+                    let mut c = current.lock().unwrap();
+                    c.agent = Arc::new(agent);
+                    c.name = name;
+                    c.finished = false;
+                }
+                let name = current.lock().unwrap().name.clone();
+                if forward_events(&mut seat_events, &name, &tx) {
+                    let side = {
+                        let mut c = current.lock().unwrap();
+                        c.finished = true;
+                        c.side
+                    };
+                    let participant =
+                        rematch::Participant::new(tx.clone(), next_tx.clone(), &nickname);
+                    rematch::join(&hub, &name, side, settings, participant).await;
+                }
+                tokio::task::yield_now().await;
+            }
+        }
+        .in_current_span(),
+    );
+}
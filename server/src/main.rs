@@ -4,7 +4,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use warp::{hyper::Uri, Filter};
 use warp::{
     path,
@@ -17,6 +18,41 @@ use crossbeam_channel::{Receiver, Sender};
 use quoridor_core::{rulebooks::*, *};
 use std::error::Error;
 use tbmp::*;
+use tracing::{error, info, info_span, warn, Instrument};
+use tracing_subscriber::EnvFilter;
+
+macro_rules! warpify {
+    ($x:ident) => {{
+        let c = $x.clone();
+        warp::any().map(move || c.clone())
+    }};
+}
+
+mod config;
+mod correspondence;
+mod engine;
+mod events;
+mod feed;
+mod json;
+mod metrics;
+mod puzzle;
+mod rematch;
+mod tournament;
+mod webhook;
+
+use common::{
+    ai::Rng,
+    board::{Board, GameSize, SizedGame, BOARD_SIZE, BOARD_SIZES},
+    control::{ClientControl, ServerControl},
+    notation::format_move,
+    record::Termination,
+};
+use config::{Config, LogFormat};
+use events::{announce, Events, ServerEvent};
+use feed::{Listings, LobbyInfo};
+use metrics::Metrics;
+use rematch::Rematches;
+use tournament::Tournaments;
 
 generate_rulebook! {
     StandardQuoridor,
@@ -24,20 +60,192 @@ generate_rulebook! {
 }
 
 type GameFn = Box<dyn Send + Sync + FnMut() -> Result<MoveResult, Box<dyn Error>>>;
-type Lobbies = Arc<RwLock<HashMap<String, (Vec<QAgent>, QGameType, GameFn)>>>;
+
+impl SizedGame for QGameType {
+    type Game = (Vec<QAgent>, GameFn);
+
+    fn standard_game(&self) -> Self::Game {
+        self.new_game()
+    }
+}
+type Lobbies = Arc<RwLock<HashMap<String, Lobby>>>;
 type Games = Arc<RwLock<HashMap<String, GameFn>>>;
+/// The sending half of a connection's websocket.
+type Out = mpsc::UnboundedSender<Result<Message, warp::Error>>;
+
+struct Lobby {
+    /// Agents indexed by side, taken as players join.
+    agents: Vec<Option<QAgent>>,
+    game_type: QGameType,
+    size: u8,
+    game: GameFn,
+    details: LobbyDetails,
+    /// The side kept for whoever joins first, normally the lobby's creator.
+    creator_side: Option<PlayerID>,
+    /// Nicknames of the seated players, by side.
+    players: Vec<Option<String>>,
+    /// Seconds since the Unix epoch.
+    created_at: u64,
+}
+
+/// What the creator of a lobby told us about it, for the listings.
+#[derive(Debug, Clone, Default)]
+struct LobbyDetails {
+    creator: Option<String>,
+    /// Advertised only, nothing keeps time yet.
+    time_control: Option<String>,
+    /// Left out of the public listings, so only those given the link join.
+    private: bool,
+}
+
+/// What a lobby was created with, which a rematch repeats.
+#[derive(Debug, Clone, Copy)]
+struct LobbySettings {
+    game_type: QGameType,
+    size: u8,
+    players: usize,
+}
 
-#[derive(Serialize, Deserialize)]
+/// The server wide state the hosts of a game share.
+#[derive(Clone)]
+struct Hub {
+    games: Games,
+    lobbies: Lobbies,
+    events: Events,
+    rematches: Rematches,
+    metrics: Arc<Metrics>,
+}
+
+impl Lobby {
+    fn settings(&self) -> LobbySettings {
+        LobbySettings {
+            game_type: self.game_type,
+            size: self.size,
+            players: self.agents.len(),
+        }
+    }
+
+    fn new(game_type: QGameType, size: u8) -> Self {
+        let (agents, game) = game_type
+            .new_game_with_size(size)
+            .expect("lobby sizes are checked against BOARD_SIZES");
+        let players = vec![None; agents.len()];
+        Lobby {
+            agents: agents.into_iter().map(Some).collect(),
+            game_type,
+            size,
+            game,
+            details: LobbyDetails::default(),
+            creator_side: None,
+            players,
+            created_at: unix_time(),
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+#[derive(Deserialize)]
 struct LobbyRequest {
     game_type: String,
     name: String,
+    #[serde(default = "default_size")]
+    size: u8,
+    #[serde(default)]
+    creator: String,
+    #[serde(default)]
+    time_control: String,
+    /// A checkbox, so present ("on") or missing.
+    #[serde(default)]
+    private: Option<String>,
+    #[serde(default)]
+    side: SideChoice,
 }
 
-macro_rules! warpify {
-    ($x:ident) => {{
-        let c = $x.clone();
-        warp::any().map(move || c.clone())
-    }};
+/// The side a lobby's creator asked to play.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum SideChoice {
+    First,
+    Second,
+    Random,
+}
+
+impl Default for SideChoice {
+    fn default() -> Self {
+        SideChoice::Random
+    }
+}
+
+impl SideChoice {
+    fn pick(self) -> PlayerID {
+        match self {
+            SideChoice::First => 0,
+            SideChoice::Second => 1,
+            SideChoice::Random => Rng::new(unix_nanos()).below(2) as PlayerID,
+        }
+    }
+}
+
+fn default_size() -> u8 {
+    BOARD_SIZE
+}
+
+#[derive(Deserialize)]
+struct EngineRequest {
+    name: String,
+    engine: String,
+}
+
+#[derive(Deserialize)]
+struct JoinQuery {
+    #[serde(default)]
+    format: WireFormat,
+    side: Option<PlayerID>,
+    nick: Option<String>,
+}
+
+const MAX_NICKNAME: usize = 20;
+
+/// Trims a nickname, `None` for a blank one. Only letters, digits, spaces and
+/// `-_.` are allowed.
+fn parse_nickname(nick: &str) -> Result<Option<String>, Rejection> {
+    let nick = nick.trim();
+    if nick.is_empty() {
+        return Ok(None);
+    }
+    let valid = nick.chars().count() <= MAX_NICKNAME
+        && nick
+            .chars()
+            .all(|c| c.is_alphanumeric() || " -_.".contains(c));
+    if valid {
+        Ok(Some(nick.to_string()))
+    } else {
+        Err(warp::reject::custom(InvalidNickname))
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+enum WireFormat {
+    Bincode,
+    Json,
+}
+
+impl Default for WireFormat {
+    fn default() -> Self {
+        WireFormat::Bincode
+    }
 }
 
 fn gtstr(gt: &QGameType) -> &'static str {
@@ -47,78 +255,332 @@ fn gtstr(gt: &QGameType) -> &'static str {
     }
 }
 
-async fn get_lobbies(lobbies: Lobbies) -> Result<impl warp::Reply, Infallible> {
-    Ok(warp::reply::json(
-        &lobbies
-            .read()
-            .await
-            .iter()
-            .map(|(name, (_, game_type, _))| LobbyRequest {
-                game_type: gtstr(game_type).into(),
-                name: name.clone(),
-            })
-            .collect::<Vec<_>>(),
-    ))
+/// A connection's place in a game, besides its agent.
+struct Seat {
+    side: PlayerID,
+    settings: LobbySettings,
+    /// The nicknames of those seated so far, by side.
+    players: Vec<Option<String>>,
+    /// Events since the seat was taken, to hear of the players who join later.
+    events: broadcast::Receiver<ServerEvent>,
+}
+
+/// Takes a seat of a lobby: the requested side, else the creator's side for
+/// the first to join, else the last free one. The game moves to `games` once
+/// every seat is taken.
+async fn take_seat(
+    hub: &Hub,
+    name: &str,
+    side: Option<PlayerID>,
+    nickname: Option<String>,
+) -> Option<(QAgent, Seat)> {
+    let mut lobbies = hub.lobbies.write().await;
+    let lobby = lobbies.get_mut(name)?;
+    let first = lobby.agents.iter().all(Option::is_some);
+    let index = match (side, lobby.creator_side) {
+        (Some(side), _) => side as usize,
+        (None, Some(side)) if first => side as usize,
+        (None, _) => lobby.agents.iter().rposition(Option::is_some)?,
+    };
+    let agent = lobby.agents.get_mut(index)?.take()?;
+    lobby.players[index] = nickname;
+    let info = LobbyInfo::new(name, lobby);
+    announce(&hub.events, ServerEvent::LobbyUpdated(info.clone()));
+    // Still holding the lobby, so no one can join between the update and this.
+    let seat = Seat {
+        side: index as PlayerID,
+        settings: lobby.settings(),
+        players: lobby.players.clone(),
+        events: hub.events.subscribe(),
+    };
+    if lobby.agents.iter().all(Option::is_none) {
+        let lobby = lobbies.remove(name).unwrap();
+        drop(lobbies);
+        hub.games.write().await.insert(name.to_string(), lobby.game);
+        announce(&hub.events, ServerEvent::GameStarted(info));
+    }
+    Some((agent, seat))
+}
+
+/// Passes on what happens to the game `name` outside of its rulebook: the
+/// nicknames as more players sit down, and forfeits. Returns whether the game
+/// was forfeited, which its agents never hear of.
+fn forward_events(events: &mut broadcast::Receiver<ServerEvent>, name: &str, out: &Out) -> bool {
+    let mut forfeited = false;
+    while let Ok(event) = events.try_recv() {
+        match event {
+            ServerEvent::LobbyUpdated(info) if info.name == name => send_control(
+                out,
+                &ServerControl::Players {
+                    names: info.players,
+                },
+            ),
+            ServerEvent::GameEnded {
+                name: ended,
+                winner,
+                termination: Termination::Forfeit,
+            } if ended == name => {
+                forfeited = true;
+                send_control(
+                    out,
+                    &ServerControl::GameEnded {
+                        winner,
+                        termination: Termination::Forfeit,
+                    },
+                )
+            }
+            _ => {}
+        }
+    }
+    forfeited
+}
+
+fn send_control(out: &Out, control: &ServerControl) {
+    let _ = out.send(Ok(Message::text(serde_json::to_string(control).unwrap())));
+}
+
+/// Whether `qmv` is legal for `side` in `game`, as far as the server can tell
+/// before the rulebook sees it. Only the standard rules are in [`Board`], so
+/// moves in free games are left to their rulebook.
+fn is_legal(game_type: QGameType, game: &Quoridor, side: PlayerID, qmv: &Move) -> bool {
+    if game.turn_of() != side {
+        return false;
+    }
+    match game_type {
+        QGameType::StandardQuoridor => Board::from_game(game).is_legal(qmv),
+        QGameType::FreeQuoridor => true,
+    }
+}
+
+/// Plays the move `player` sent at `received` in the game `name`, announcing
+/// it. The rulebook's reason comes back if it refused the move, and the game
+/// waits for another.
+async fn step_game(
+    hub: &Hub,
+    name: &str,
+    player: PlayerID,
+    notation: String,
+    received: Instant,
+) -> Result<(), String> {
+    let refused = match hub.games.write().await.get_mut(name).map(|t| t()) {
+        Some(Ok(_)) => None,
+        Some(Err(e)) => Some(e.to_string()),
+        None => return Ok(()),
+    };
+    if let Some(reason) = refused {
+        return Err(reason);
+    }
+    hub.metrics.move_latency.observe(received.elapsed());
+    announce(
+        &hub.events,
+        ServerEvent::MovePlayed {
+            name: name.into(),
+            player,
+            notation,
+        },
+    );
+    Ok(())
+}
+
+/// Drops a finished game, announcing it the first time one of its hosts sees it end.
+async fn end_game(
+    games: &Games,
+    events: &Events,
+    name: &str,
+    winner: Option<PlayerID>,
+    termination: Termination,
+) {
+    if games.write().await.remove(name).is_some() {
+        announce(
+            events,
+            ServerEvent::GameEnded {
+                name: name.into(),
+                winner,
+                termination,
+            },
+        );
+    }
+}
+
+/// Ends the game `name` as lost by `loser`, for breaking the rules or running
+/// out of time. No agent hears of this, so the hosts tell their players.
+async fn forfeit(hub: &Hub, name: &str, loser: PlayerID, settings: LobbySettings, reason: &str) {
+    warn!(game = %name, side = loser, reason, "game forfeited");
+    let winner = (loser as usize + 1) % settings.players;
+    end_game(
+        &hub.games,
+        &hub.events,
+        name,
+        Some(winner as PlayerID),
+        Termination::Forfeit,
+    )
+    .await;
+}
+
+/// Drops the game or lobby a player walked out of.
+async fn abandon_game(hub: &Hub, name: &str) {
+    let game = hub.games.write().await.remove(name);
+    let lobby = hub.lobbies.write().await.remove(name);
+    if game.is_some() || lobby.is_some() {
+        announce(&hub.events, ServerEvent::LobbyRemoved { name: name.into() });
+    }
+}
+
+/// Logs to stderr, at the levels `RUST_LOG` asks for or from `info` up.
+fn init_logging(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let logger = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Pretty => logger.init(),
+        LogFormat::Json => logger.json().init(),
+    }
 }
 
 #[tokio::main]
 async fn main() {
-    pretty_env_logger::init();
+    let config = Arc::new(Config::load());
+    init_logging(config.log_format);
+    let puzzles = puzzle::load(config.puzzles.as_deref());
+    let correspondence = correspondence::load(config.correspondence.as_deref());
 
     let games = Games::default();
 
     let lobbies = Lobbies::default();
 
+    let mut events = events::channel();
+    if !config.webhooks.is_empty() {
+        tokio::spawn(webhook::dispatch(config.webhooks.clone(), events.queue()));
+    }
+    let results = events.queue();
+
+    let hub = Hub {
+        games: games.clone(),
+        lobbies: lobbies.clone(),
+        events: events.clone(),
+        rematches: Rematches::default(),
+        metrics: Arc::clone(events.metrics()),
+    };
+
+    let tournaments = Tournaments::default();
+    tokio::spawn(tournament::collect_results(
+        tournaments.clone(),
+        hub.clone(),
+        results,
+    ));
+
+    tokio::spawn(correspondence::enforce_deadlines(
+        correspondence.clone(),
+        events.clone(),
+    ));
+
+    let listings = Listings::default();
+    tokio::spawn(feed::track(
+        listings.clone(),
+        hub.clone(),
+        events.subscribe(),
+    ));
+
     let new_lobby = warp::post()
         .and(path!("lobby" / "new"))
         .and(parse_lobby_request())
-        .and(warpify!(lobbies))
-        .and_then(
-            |(game_type, name): (QGameType, String), lobbies: Lobbies| async move {
-                let (v, t) = game_type.new_game();
-                let gt = game_type;
-                let n = name.clone();
-                lobbies.write().await.insert(name, (v, game_type, t));
+        .and(warpify!(hub))
+        .and_then(|request: NewLobby, hub: Hub| {
+            let span = info_span!("lobby", lobby = %request.name);
+            async move {
+                let gt = request.game_type;
+                let n = request.name.clone();
+                let mut lobby = Lobby::new(request.game_type, request.size);
+                lobby.details = request.details;
+                lobby.creator_side = Some(request.creator_side);
+                announce(
+                    &hub.events,
+                    ServerEvent::LobbyCreated(LobbyInfo::new(&n, &lobby)),
+                );
+                hub.lobbies.write().await.insert(request.name, lobby);
                 Ok::<_, std::convert::Infallible>(warp::redirect(
                     Uri::builder()
                         .path_and_query(&format!("/game/{}/{}", gtstr(&gt), n)[..])
                         .build()
                         .unwrap(),
                 ))
-            },
-        );
-
-    let lobby_list = warp::get()
-        .and(path!("lobby" / "list"))
-        .and(warpify!(lobbies))
-        .and_then(get_lobbies);
+            }
+            .instrument(span)
+        });
 
     let join = warp::get()
         .and(path!("join" / String))
-        .and(warpify!(lobbies))
-        .and(warpify!(games))
+        .and(warp::query::<JoinQuery>())
+        .and(warpify!(hub))
         .and(warp::ws())
-        .map(
-            |name: String, lobbies: Lobbies, games: Games, socket: warp::ws::Ws| {
-                socket.on_upgrade(|socket| async move {
-                    let arc = Clone::clone(&lobbies);
-                    let mut lobbies = lobbies.write().await;
-                    let agent = lobbies.get_mut(&name).unwrap().0.pop().unwrap();
-                    if lobbies.get(&name).unwrap().0.len() == 0 {
-                        let game = lobbies.remove(&name).unwrap();
-                        drop(lobbies);
-                        games.write().await.insert(name.clone(), game.2);
-                    } else {
-                        drop(lobbies);
-                    }
-                    match agent {
-                        QAgent::StandardQuoridor(c) => c.host(socket, games, arc, name),
-                        QAgent::FreeQuoridor(c) => c.host(socket, games, arc, name),
+        .and_then(
+            |name: String, query: JoinQuery, hub: Hub, socket: warp::ws::Ws| async move {
+                let nickname = parse_nickname(query.nick.as_deref().unwrap_or(""))?;
+                Ok::<_, Rejection>(socket.on_upgrade(move |socket| {
+                    // The hosts' tasks run in this span too, so every line about
+                    // the connection says whose it is.
+                    let span = info_span!(
+                        "connection",
+                        game = %name,
+                        format = ?query.format,
+                        side = tracing::field::Empty
+                    );
+                    async move {
+                        let (agent, seat) = match take_seat(&hub, &name, query.side, nickname).await
+                        {
+                            Some(seat) => seat,
+                            None => {
+                                info!("no seat to take");
+                                return;
+                            }
+                        };
+                        tracing::Span::current().record("side", &seat.side);
+                        info!("connected");
+                        match (query.format, agent) {
+                            (WireFormat::Json, agent) => json::host(agent, socket, hub, name, seat),
+                            (WireFormat::Bincode, QAgent::StandardQuoridor(c)) => {
+                                c.host(socket, hub, name, seat)
+                            }
+                            (WireFormat::Bincode, QAgent::FreeQuoridor(c)) => {
+                                c.host(socket, hub, name, seat)
+                            }
+                        }
                     }
-                })
+                    .instrument(span)
+                }))
+            },
+        );
+
+    let add_engine = warp::post()
+        .and(path!("lobby" / "engine"))
+        .and(warp::body::form())
+        .and(warpify!(config))
+        .and(warpify!(hub))
+        .and_then(
+            |request: EngineRequest, config: Arc<Config>, hub: Hub| async move {
+                let engine = config
+                    .engine(&request.engine)
+                    .ok_or_else(|| warp::reject::custom(UnknownEngine))?
+                    .clone();
+                let (agent, seat) = take_seat(&hub, &request.name, None, Some(engine.name.clone()))
+                    .await
+                    .ok_or_else(warp::reject::not_found)?;
+                let path = format!("/game/{}/{}", gtstr(&seat.settings.game_type), request.name);
+                engine::host(agent, engine, hub, request.name, seat.settings);
+                Ok::<_, Rejection>(warp::redirect(
+                    Uri::builder().path_and_query(&path[..]).build().unwrap(),
+                ))
             },
         );
 
+    let engine_list = warp::get()
+        .and(path!("engine" / "list"))
+        .and(warpify!(config))
+        .map(|config: Arc<Config>| {
+            warp::reply::json(&config.engines.iter().map(|e| &e.name).collect::<Vec<_>>())
+        });
+
     //let game = warp::path::end().map(|| warp::reply::html(GAME_HTML));
     let game = path!("game" / String / String)
         .and(warp::fs::file("./static/game.html"))
@@ -132,8 +594,16 @@ async fn main() {
 
     let routes = index
         .or(game)
-        .or(lobby_list)
+        .or(feed::list(listings.clone()))
+        .or(feed::info(listings.clone()))
+        .or(feed::route(listings, events.clone()))
         .or(new_lobby)
+        .or(add_engine)
+        .or(engine_list)
+        .or(metrics::route(hub.clone()))
+        .or(tournament::routes(tournaments, hub.clone()))
+        .or(puzzle::routes(puzzles))
+        .or(correspondence::routes(correspondence, events))
         .or(join)
         .or(path("static").and(
             warp::fs::dir("./static")
@@ -143,67 +613,268 @@ async fn main() {
     warp::serve(routes).run(([0, 0, 0, 0], 3030)).await;
 }
 
-fn parse_lobby_request() -> impl Filter<Extract = ((QGameType, String),), Error = Rejection> + Copy
-{
+/// A validated `LobbyRequest`.
+struct NewLobby {
+    game_type: QGameType,
+    size: u8,
+    name: String,
+    details: LobbyDetails,
+    creator_side: PlayerID,
+}
+
+fn parse_lobby_request() -> impl Filter<Extract = (NewLobby,), Error = Rejection> + Copy {
     warp::body::form().and_then(|gt: LobbyRequest| async move {
         let game_type = match &gt.game_type[..] {
             "standard" => QGameType::StandardQuoridor,
             "free" => QGameType::FreeQuoridor,
             _ => return Err(warp::reject::custom(UnimplementedGameType)),
         };
+        if !BOARD_SIZES.contains(&gt.size) {
+            return Err(warp::reject::custom(UnsupportedBoardSize));
+        }
 
-        Ok((game_type, gt.name))
+        let filled = |s: String| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+        let details = LobbyDetails {
+            creator: filled(gt.creator),
+            time_control: filled(gt.time_control),
+            private: gt.private.is_some(),
+        };
+        Ok(NewLobby {
+            game_type,
+            size: gt.size,
+            name: gt.name,
+            details,
+            creator_side: gt.side.pick(),
+        })
     })
 }
 
+/// Recovers the rulebook's own agent from a `QAgent`, for the generic host.
+trait FromQAgent: Sized {
+    fn from_qagent(agent: QAgent) -> Option<Self>;
+}
+
+impl FromQAgent for AgentCore<QGame<StandardQuoridor>> {
+    fn from_qagent(agent: QAgent) -> Option<Self> {
+        match agent {
+            QAgent::StandardQuoridor(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+impl FromQAgent for AgentCore<QGame<FreeQuoridor>> {
+    fn from_qagent(agent: QAgent) -> Option<Self> {
+        match agent {
+            QAgent::FreeQuoridor(c) => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/// Writes a rulebook's moves in notation, for the events they're announced in.
+trait MoveNotation: Game {
+    fn notation(qmv: &Self::Move) -> String;
+}
+
+impl MoveNotation for QGame<StandardQuoridor> {
+    fn notation(qmv: &Self::Move) -> String {
+        format_move(qmv)
+    }
+}
+
+impl MoveNotation for QGame<FreeQuoridor> {
+    fn notation(qmv: &Self::Move) -> String {
+        format_move(qmv)
+    }
+}
+
+/// The game a connection is playing, which changes with rematches.
+struct Current<A> {
+    /// Where the connection's moves go.
+    agent: A,
+    name: String,
+    side: PlayerID,
+    finished: bool,
+}
+
 trait WSHost {
-    fn host(self, socket: WebSocket, games: Games, lobbies: Lobbies, name: String);
+    fn host(self, socket: WebSocket, hub: Hub, name: String, seat: Seat);
 }
 
-impl<G: Game> WSHost for AgentCore<G> {
-    fn host(self, socket: WebSocket, games: Games, lobbies: Lobbies, name: String) {
+impl<G: MoveNotation> WSHost for AgentCore<G>
+where
+    AgentCore<G>: FromQAgent,
+{
+    fn host(self, socket: WebSocket, hub: Hub, name: String, seat: Seat) {
         let (wstx, mut wsrx) = socket.split();
 
         let (tx, rx) = mpsc::unbounded_channel();
         //let quit_tx = tx.clone();
         tokio::spawn(rx.forward(wstx));
-        let mc = self.move_channel;
-        let ec = self.event_channel;
-        tokio::spawn(async move {
-            while let Some(result) = wsrx.next().await {
-                match result {
-                    Ok(msg) => {
-                        let buf = msg.as_bytes();
-                        if let Ok(qmv) = bincode::deserialize::<G::Move>(buf) {
-                            mc.send(qmv).unwrap();
-                            if let Some(t) = games.write().await.get_mut(&name) {
-                                t().unwrap();
+        let Seat {
+            side,
+            settings,
+            players,
+            events: mut seat_events,
+        } = seat;
+        let nickname = players[side as usize].clone();
+        send_control(&tx, &ServerControl::Seated { side });
+        send_control(&tx, &ServerControl::Players { names: players });
+        let (next_tx, mut next_rx) = mpsc::unbounded_channel();
+        let current = Arc::new(std::sync::Mutex::new(Current {
+            agent: self.move_channel,
+            name,
+            side,
+            finished: false,
+        }));
+
+        let mut ec = self.event_channel;
+        let out = tx.clone();
+        let (in_hub, in_current) = (hub.clone(), current.clone());
+        tokio::spawn(
+            async move {
+                let (hub, current) = (in_hub, in_current);
+                let _connection = hub.metrics.connect();
+                while let Some(result) = wsrx.next().await {
+                    match result {
+                        Ok(msg) => {
+                            let received = Instant::now();
+                            let (name, side, finished) = {
+                                let c = current.lock().unwrap();
+                                (c.name.clone(), c.side, c.finished)
+                            };
+                            // A close frame ends the game.
+                            if !msg.is_close() {
+                                if let Ok(text) = msg.to_str() {
+                                    // Text frames carry control messages, see `common::control`.
+                                    match serde_json::from_str(text) {
+                                        Ok(ClientControl::RematchOffer) if finished => {
+                                            rematch::offer(&hub, &name, side).await
+                                        }
+                                        Ok(ClientControl::RematchDecline) if finished => {
+                                            rematch::leave(&hub, &name, side, settings).await
+                                        }
+                                        _ => {}
+                                    }
+                                    continue;
+                                }
+                                // Pings and pongs.
+                                if !msg.is_binary() {
+                                    continue;
+                                }
+                                match bincode::deserialize::<G::Move>(msg.as_bytes()) {
+                                    Ok(qmv) => {
+                                        let notation = G::notation(&qmv);
+                                        // Nothing takes moves once a game has been forfeited.
+                                        if current.lock().unwrap().agent.send(qmv).is_err() {
+                                            continue;
+                                        }
+                                        if let Err(reason) =
+                                            step_game(&hub, &name, side, notation, received).await
+                                        {
+                                            warn!(game = %name, error = %reason, "move refused");
+                                            send_control(
+                                                &out,
+                                                &ServerControl::MoveRefused { reason },
+                                            );
+                                        }
+                                    }
+                                    Err(e) => {
+                                        hub.metrics.bincode_decode_failures.inc();
+                                        forfeit(&hub, &name, side, settings, &e.to_string()).await;
+                                    }
+                                }
+                                continue;
                             }
-                        } else {
                             //let buf = bincode::serialize(&GameEvent::<G>::OpponentQuit).unwrap();
-                            eprintln!("Someone quit!");
-                            games.write().await.remove(&name);
-                            lobbies.write().await.remove(&name);
+                            info!(game = %name, "player quit");
+                            if finished {
+                                rematch::leave(&hub, &name, side, settings).await;
+                            }
+                            abandon_game(&hub, &name).await;
                             //quit_tx.send(Ok(Message::binary(buf))).unwrap();
                         }
+                        Err(e) => {
+                            warn!(error = %e, "connection failed");
+                            break;
+                        }
                     }
-                    Err(_) => break,
                 }
+                info!("disconnected");
             }
-        });
+            .in_current_span(),
+        );
 
-        tokio::spawn(async move {
-            loop {
-                if let Ok(msg) = ec.try_recv() {
-                    let buf = bincode::serialize(&msg).unwrap();
-                    tx.send(Ok(Message::binary(buf))).unwrap();
+        tokio::spawn(
+            async move {
+                loop {
+                    if let Ok(msg) = ec.try_recv() {
+                        let buf = bincode::serialize(&msg).unwrap();
+                        // Nobody is listening once the connection has closed.
+                        if tx.send(Ok(Message::binary(buf))).is_err() {
+                            break;
+                        }
+                        match msg {
+                            GameEvent::GameStart(_, side) => current.lock().unwrap().side = side,
+                            GameEvent::GameEnd(winner) => {
+                                let (name, side) = {
+                                    let mut c = current.lock().unwrap();
+                                    c.finished = true;
+                                    (c.name.clone(), c.side)
+                                };
+                                end_game(&hub.games, &hub.events, &name, winner, Termination::Goal)
+                                    .await;
+                                let participant = rematch::Participant::new(
+                                    tx.clone(),
+                                    next_tx.clone(),
+                                    &nickname,
+                                );
+                                rematch::join(&hub, &name, side, settings, participant).await;
+                            }
+                            _ => {}
+                        }
+                    }
+                    let name = current.lock().unwrap().name.clone();
+                    if forward_events(&mut seat_events, &name, &tx) {
+                        let side = {
+                            let mut c = current.lock().unwrap();
+                            c.finished = true;
+                            c.side
+                        };
+                        let participant =
+                            rematch::Participant::new(tx.clone(), next_tx.clone(), &nickname);
+                        rematch::join(&hub, &name, side, settings, participant).await;
+                    }
+                    if let Ok((agent, name)) = next_rx.try_recv() {
+                        if let Some(core) = AgentCore::<G>::from_qagent(agent) {
+                            ec = core.event_channel;
+                            let mut c = current.lock().unwrap();
+                            c.agent = core.move_channel;
+                            c.name = name;
+                            c.finished = false;
+                        }
+                    }
+                    tokio::task::yield_now().await;
                 }
-                tokio::task::yield_now().await;
             }
-        });
+            .in_current_span(),
+        );
     }
 }
 
 #[derive(Debug)]
 struct UnimplementedGameType;
 impl warp::reject::Reject for UnimplementedGameType {}
+
+#[derive(Debug)]
+struct UnsupportedBoardSize;
+impl warp::reject::Reject for UnsupportedBoardSize {}
+
+#[derive(Debug)]
+struct InvalidNickname;
+impl warp::reject::Reject for InvalidNickname {}
+
+#[derive(Debug)]
+struct UnknownEngine;
+impl warp::reject::Reject for UnknownEngine {}
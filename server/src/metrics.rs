@@ -0,0 +1,356 @@
+//! How the server is doing, served at `/metrics` in the Prometheus text format.
+//!
+//! Lobby and game lifecycle counts are taken as the events are announced.
+//! Connections, malformed messages and how long moves take to go through are
+//! counted by the hosts themselves, through the `Metrics` in the `Hub`.
+
+use super::*;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+const BUCKETS: usize = 10;
+/// Upper bounds of the move processing time buckets, in seconds.
+const LATENCY_BUCKETS: [f64; BUCKETS] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0,
+];
+
+#[derive(Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Times between a move arriving and the game taking it.
+#[derive(Default)]
+pub struct Histogram {
+    /// By bucket, each counting only the times above the bucket before it; the
+    /// last counts those above every bound.
+    buckets: [Counter; BUCKETS + 1],
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    pub fn observe(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(BUCKETS);
+        self.buckets[bucket].inc();
+        self.sum_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    lobbies_created: Counter,
+    games_started: Counter,
+    games_ended: Counter,
+    games_drawn: Counter,
+    moves: Counter,
+    connections_opened: Counter,
+    connections_closed: Counter,
+    pub bincode_decode_failures: Counter,
+    pub json_decode_failures: Counter,
+    pub move_latency: Histogram,
+}
+
+/// An open WebSocket connection, counted as closed once dropped.
+pub struct Connection(Arc<Metrics>);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.connections_closed.inc();
+    }
+}
+
+impl Metrics {
+    /// Counts a connection opening, and later closing when the guard drops.
+    pub fn connect(self: &Arc<Self>) -> Connection {
+        self.connections_opened.inc();
+        Connection(Arc::clone(self))
+    }
+
+    /// Counts a lifecycle event as it's announced.
+    pub fn count(&self, event: &ServerEvent) {
+        match event {
+            ServerEvent::LobbyCreated(_) => self.lobbies_created.inc(),
+            ServerEvent::GameStarted(_) => self.games_started.inc(),
+            ServerEvent::MovePlayed { .. } => self.moves.inc(),
+            ServerEvent::GameEnded { winner, .. } => {
+                self.games_ended.inc();
+                if winner.is_none() {
+                    self.games_drawn.inc();
+                }
+            }
+            ServerEvent::LobbyUpdated(_) | ServerEvent::LobbyRemoved { .. } => {}
+        }
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn single(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    family(out, name, kind, help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// The metrics in the text format, along with how many lobbies are open and
+/// games active.
+fn render(metrics: &Metrics, lobbies: u64, games: u64) -> String {
+    let mut out = String::new();
+
+    single(
+        &mut out,
+        "qweb_lobbies_open",
+        "gauge",
+        "Lobbies waiting for players.",
+        lobbies,
+    );
+    single(
+        &mut out,
+        "qweb_games_active",
+        "gauge",
+        "Live games being played.",
+        games,
+    );
+    single(
+        &mut out,
+        "qweb_lobbies_created_total",
+        "counter",
+        "Lobbies opened, rematches included.",
+        metrics.lobbies_created.get(),
+    );
+    single(
+        &mut out,
+        "qweb_games_started_total",
+        "counter",
+        "Games started, correspondence games included.",
+        metrics.games_started.get(),
+    );
+
+    family(
+        &mut out,
+        "qweb_games_ended_total",
+        "counter",
+        "Games ended, by result.",
+    );
+    // Read in the opposite order of counting, so the difference can't go below zero.
+    let drawn = metrics.games_drawn.get();
+    let ended = metrics.games_ended.get();
+    let _ = writeln!(
+        out,
+        "qweb_games_ended_total{{result=\"won\"}} {}",
+        ended - drawn
+    );
+    let _ = writeln!(out, "qweb_games_ended_total{{result=\"drawn\"}} {}", drawn);
+
+    single(
+        &mut out,
+        "qweb_moves_total",
+        "counter",
+        "Moves played, correspondence games included.",
+        metrics.moves.get(),
+    );
+
+    let closed = metrics.connections_closed.get();
+    let opened = metrics.connections_opened.get();
+    single(
+        &mut out,
+        "qweb_websocket_connections",
+        "gauge",
+        "Open game connections.",
+        opened - closed,
+    );
+    single(
+        &mut out,
+        "qweb_websocket_connections_total",
+        "counter",
+        "Game connections opened.",
+        opened,
+    );
+    single(
+        &mut out,
+        "qweb_websocket_disconnects_total",
+        "counter",
+        "Game connections closed.",
+        closed,
+    );
+
+    family(
+        &mut out,
+        "qweb_decode_failures_total",
+        "counter",
+        "Messages that couldn't be read as a move, by wire format.",
+    );
+    let _ = writeln!(
+        out,
+        "qweb_decode_failures_total{{format=\"bincode\"}} {}",
+        metrics.bincode_decode_failures.get()
+    );
+    let _ = writeln!(
+        out,
+        "qweb_decode_failures_total{{format=\"json\"}} {}",
+        metrics.json_decode_failures.get()
+    );
+
+    let name = "qweb_move_processing_seconds";
+    family(
+        &mut out,
+        name,
+        "histogram",
+        "Time from a live game move arriving to the game taking it.",
+    );
+    let histogram = &metrics.move_latency;
+    let mut count = 0;
+    for (bucket, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS.iter()) {
+        count += bucket.get();
+        let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+    }
+    count += histogram.buckets[BUCKETS].get();
+    let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+    let sum = histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1e6;
+    let _ = writeln!(out, "{}_sum {}", name, sum);
+    let _ = writeln!(out, "{}_count {}", name, count);
+    out
+}
+
+pub fn route(hub: Hub) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
+    warp::get()
+        .and(path!("metrics"))
+        .and(warpify!(hub))
+        .and_then(|hub: Hub| async move {
+            let lobbies = hub.lobbies.read().await.len() as u64;
+            let games = hub.games.read().await.len() as u64;
+            Ok::<_, Infallible>(warp::reply::with_header(
+                render(&hub.metrics, lobbies, games),
+                "Content-Type",
+                "text/plain; version=0.0.4",
+            ))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> LobbySettings {
+        LobbySettings {
+            game_type: QGameType::StandardQuoridor,
+            size: BOARD_SIZE,
+            players: 2,
+        }
+    }
+
+    fn lines(text: &str) -> Vec<&str> {
+        text.lines().filter(|l| !l.starts_with('#')).collect()
+    }
+
+    #[test]
+    fn counts_events() {
+        let metrics = Arc::new(Metrics::default());
+        let info = LobbyInfo::with_settings("friday", settings(), 2);
+        metrics.count(&ServerEvent::LobbyCreated(info.clone()));
+        metrics.count(&ServerEvent::LobbyUpdated(info.clone()));
+        metrics.count(&ServerEvent::GameStarted(info));
+        for (player, notation) in [(0, "e2"), (1, "e8")].iter() {
+            metrics.count(&ServerEvent::MovePlayed {
+                name: "friday".into(),
+                player: *player,
+                notation: notation.to_string(),
+            });
+        }
+        for winner in [Some(0), None, Some(1)].iter() {
+            metrics.count(&ServerEvent::GameEnded {
+                name: "friday".into(),
+                winner: *winner,
+                termination: Termination::Goal,
+            });
+        }
+        let _open = metrics.connect();
+        drop(metrics.connect());
+        metrics.json_decode_failures.inc();
+
+        let text = render(&metrics, 3, 1);
+        assert_eq!(
+            &lines(&text)[..14],
+            &[
+                "qweb_lobbies_open 3",
+                "qweb_games_active 1",
+                "qweb_lobbies_created_total 1",
+                "qweb_games_started_total 1",
+                "qweb_games_ended_total{result=\"won\"} 2",
+                "qweb_games_ended_total{result=\"drawn\"} 1",
+                "qweb_moves_total 2",
+                "qweb_websocket_connections 1",
+                "qweb_websocket_connections_total 2",
+                "qweb_websocket_disconnects_total 1",
+                "qweb_decode_failures_total{format=\"bincode\"} 0",
+                "qweb_decode_failures_total{format=\"json\"} 1",
+                "qweb_move_processing_seconds_bucket{le=\"0.0005\"} 0",
+                "qweb_move_processing_seconds_bucket{le=\"0.001\"} 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn families_are_described() {
+        let text = render(&Metrics::default(), 0, 0);
+        assert!(text.contains("# HELP qweb_moves_total "));
+        assert!(text.contains("# TYPE qweb_moves_total counter\n"));
+        assert!(text.contains("# TYPE qweb_websocket_connections gauge\n"));
+        assert!(text.contains("# TYPE qweb_move_processing_seconds histogram\n"));
+        // Every sample belongs to the family described before it.
+        let mut family = "";
+        for line in text.lines() {
+            if line.starts_with("# TYPE ") {
+                family = line.split(' ').nth(2).unwrap();
+            } else if !line.starts_with('#') {
+                assert!(line.starts_with(family), "{} outside {}", line, family);
+            }
+        }
+    }
+
+    #[test]
+    fn latency_buckets_are_cumulative() {
+        let metrics = Metrics::default();
+        for &millis in &[0, 3, 3, 40, 2000] {
+            metrics.move_latency.observe(Duration::from_millis(millis));
+        }
+        let text = render(&metrics, 0, 0);
+        let samples: Vec<_> = lines(&text)
+            .into_iter()
+            .filter(|l| l.starts_with("qweb_move_processing_seconds"))
+            .collect();
+        assert_eq!(
+            samples,
+            vec![
+                "qweb_move_processing_seconds_bucket{le=\"0.0005\"} 1",
+                "qweb_move_processing_seconds_bucket{le=\"0.001\"} 1",
+                "qweb_move_processing_seconds_bucket{le=\"0.0025\"} 1",
+                "qweb_move_processing_seconds_bucket{le=\"0.005\"} 3",
+                "qweb_move_processing_seconds_bucket{le=\"0.01\"} 3",
+                "qweb_move_processing_seconds_bucket{le=\"0.025\"} 3",
+                "qweb_move_processing_seconds_bucket{le=\"0.05\"} 4",
+                "qweb_move_processing_seconds_bucket{le=\"0.1\"} 4",
+                "qweb_move_processing_seconds_bucket{le=\"0.25\"} 4",
+                "qweb_move_processing_seconds_bucket{le=\"1\"} 4",
+                "qweb_move_processing_seconds_bucket{le=\"+Inf\"} 5",
+                "qweb_move_processing_seconds_sum 2.046",
+                "qweb_move_processing_seconds_count 5",
+            ]
+        );
+    }
+}
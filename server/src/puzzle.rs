@@ -0,0 +1,80 @@
+use super::*;
+use common::{
+    board::Board,
+    puzzle::{self, Puzzle},
+};
+use std::path::Path;
+
+const DEFAULT_PUZZLES_PATH: &str = "./puzzles.txt";
+
+pub type Puzzles = Arc<Vec<Puzzle>>;
+
+#[derive(Serialize)]
+struct PuzzleSummary<'a> {
+    id: &'a str,
+    title: &'a str,
+    size: u8,
+}
+
+/// The starting position of a standard game on a board of `size`.
+pub fn start_position(size: u8) -> Option<Board> {
+    let (agents, _) = QGameType::StandardQuoridor.new_game_with_size(size)?;
+    match agents.first()?.recv_event().ok()? {
+        QGameEvent::GameStart(game, _) => Some(Board::from_game(&game)),
+        _ => None,
+    }
+}
+
+/// Loads the puzzle file, making sure every puzzle can be played out. There are
+/// no puzzles if there is no file.
+pub fn load(path: Option<&Path>) -> Puzzles {
+    let path = path.unwrap_or_else(|| Path::new(DEFAULT_PUZZLES_PATH));
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(_) => return Puzzles::default(),
+    };
+    let puzzles = puzzle::parse(&text)
+        .and_then(|puzzles| {
+            for puzzle in &puzzles {
+                let start = start_position(puzzle.size)
+                    .unwrap_or_else(|| panic!("no starting position for size {}", puzzle.size));
+                puzzle.check(&start)?;
+            }
+            Ok(puzzles)
+        })
+        .unwrap_or_else(|e| panic!("invalid puzzle file {}: {}", path.display(), e));
+    Arc::new(puzzles)
+}
+
+/// `GET /puzzle/list` lists the puzzles, `GET /puzzle/{id}` gives one in full.
+pub fn routes(
+    puzzles: Puzzles,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = Rejection> + Clone {
+    let list = warp::get()
+        .and(path!("puzzle" / "list"))
+        .and(warpify!(puzzles))
+        .map(|puzzles: Puzzles| {
+            let summaries: Vec<_> = puzzles
+                .iter()
+                .map(|p| PuzzleSummary {
+                    id: &p.id,
+                    title: &p.title,
+                    size: p.size,
+                })
+                .collect();
+            warp::reply::json(&summaries)
+        });
+
+    let get = warp::get()
+        .and(path!("puzzle" / String))
+        .and(warpify!(puzzles))
+        .and_then(|id: String, puzzles: Puzzles| async move {
+            let puzzle = puzzles
+                .iter()
+                .find(|p| p.id == id)
+                .ok_or_else(warp::reject::not_found)?;
+            Ok::<_, Rejection>(warp::reply::json(puzzle))
+        });
+
+    list.or(get)
+}
@@ -0,0 +1,174 @@
+use super::*;
+
+/// Finished games whose players may still agree to a rematch, by game name.
+pub type Rematches = Arc<RwLock<HashMap<String, Finished>>>;
+
+/// Where a host is handed the agent and name of its next game.
+pub type NextGame = mpsc::UnboundedSender<(QAgent, String)>;
+
+pub struct Participant {
+    out: Out,
+    next: NextGame,
+    nickname: Option<String>,
+    offered: bool,
+}
+
+impl Participant {
+    pub fn new(out: Out, next: NextGame, nickname: &Option<String>) -> Self {
+        Participant {
+            out,
+            next,
+            nickname: nickname.clone(),
+            offered: false,
+        }
+    }
+}
+
+pub struct Finished {
+    settings: LobbySettings,
+    /// Players still waiting to hear about a rematch, by their side in the finished game.
+    seats: HashMap<PlayerID, Participant>,
+    /// How many players left or declined.
+    left: usize,
+}
+
+impl Finished {
+    fn new(settings: LobbySettings) -> Self {
+        Finished {
+            settings,
+            seats: HashMap::new(),
+            left: 0,
+        }
+    }
+
+    fn declined(&self) -> bool {
+        self.left > 0
+    }
+}
+
+/// Names the game after `name`: `game-rematch2` follows `game-rematch` follows `game`.
+fn rematch_name(name: &str) -> String {
+    if let Some(i) = name.rfind("-rematch") {
+        let (base, suffix) = name.split_at(i);
+        match &suffix["-rematch".len()..] {
+            "" => return format!("{}-rematch2", base),
+            n => {
+                if let Ok(n) = n.parse::<u32>() {
+                    return format!("{}-rematch{}", base, n + 1);
+                }
+            }
+        }
+    }
+    format!("{}-rematch", name)
+}
+
+/// Makes a player who has seen `name` end available for a rematch.
+pub async fn join(
+    hub: &Hub,
+    name: &str,
+    side: PlayerID,
+    settings: LobbySettings,
+    participant: Participant,
+) {
+    let mut rematches = hub.rematches.write().await;
+    let finished = rematches
+        .entry(name.to_string())
+        .or_insert_with(|| Finished::new(settings));
+    if finished.declined() {
+        send_control(&participant.out, &ServerControl::RematchDeclined);
+    }
+    finished.seats.insert(side, participant);
+}
+
+/// Records a rematch offer, starting the rematch once every player has made one.
+pub async fn offer(hub: &Hub, name: &str, side: PlayerID) {
+    let mut rematches = hub.rematches.write().await;
+    let finished = match rematches.get_mut(name) {
+        Some(finished) if !finished.declined() => finished,
+        _ => return,
+    };
+    match finished.seats.get_mut(&side) {
+        Some(participant) => participant.offered = true,
+        None => return,
+    }
+    info!(game = %name, side, "rematch offered");
+    for (_, participant) in finished.seats.iter().filter(|(&s, _)| s != side) {
+        send_control(&participant.out, &ServerControl::RematchOffered { side });
+    }
+
+    let ready = finished.seats.len() == finished.settings.players
+        && finished.seats.values().all(|p| p.offered);
+    if ready {
+        let finished = rematches.remove(name).unwrap();
+        drop(rematches);
+        start(hub, name, finished).await;
+    }
+}
+
+async fn start(hub: &Hub, name: &str, finished: Finished) {
+    let settings = finished.settings;
+    let Lobby {
+        mut agents, game, ..
+    } = Lobby::new(settings.game_type, settings.size);
+    // Someone may have taken the next name meanwhile, so skip on to a free one.
+    let mut next_name = rematch_name(name);
+    {
+        let lobbies = hub.lobbies.read().await;
+        let mut games = hub.games.write().await;
+        while lobbies.contains_key(&next_name) || games.contains_key(&next_name) {
+            next_name = rematch_name(&next_name);
+        }
+        games.insert(next_name.clone(), game);
+    }
+    info!(game = %name, rematch = %next_name, "rematch starting");
+
+    // Sides swap, so the first move changes hands.
+    let swapped = |side: PlayerID| (side as usize + 1) % settings.players;
+    let mut players = vec![None; settings.players];
+    for (&side, participant) in &finished.seats {
+        players[swapped(side)] = participant.nickname.clone();
+    }
+    let mut listing = LobbyInfo::with_settings(&next_name, settings, settings.players);
+    listing.players = players.clone();
+    announce(&hub.events, ServerEvent::LobbyCreated(listing.clone()));
+    announce(&hub.events, ServerEvent::GameStarted(listing));
+
+    for (side, participant) in finished.seats {
+        let next_side = swapped(side);
+        if let Some(agent) = agents[next_side].take() {
+            send_control(
+                &participant.out,
+                &ServerControl::RematchStarting {
+                    side: next_side as PlayerID,
+                },
+            );
+            send_control(
+                &participant.out,
+                &ServerControl::Players {
+                    names: players.clone(),
+                },
+            );
+            let _ = participant.next.send((agent, next_name.clone()));
+        }
+    }
+}
+
+/// Takes a player who declined or disconnected out of the running, which calls
+/// the rematch off for everyone.
+pub async fn leave(hub: &Hub, name: &str, side: PlayerID, settings: LobbySettings) {
+    let mut rematches = hub.rematches.write().await;
+    let finished = rematches
+        .entry(name.to_string())
+        .or_insert_with(|| Finished::new(settings));
+    finished.seats.remove(&side);
+    finished.left += 1;
+    if finished.left == 1 {
+        info!(game = %name, side, "rematch declined");
+        for participant in finished.seats.values() {
+            send_control(&participant.out, &ServerControl::RematchDeclined);
+        }
+    }
+    if finished.left >= finished.settings.players {
+        rematches.remove(name);
+    }
+}
@@ -0,0 +1,802 @@
+use super::*;
+use warp::{http::StatusCode, Reply};
+
+pub type Tournaments = Arc<RwLock<HashMap<String, Tournament>>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    RoundRobin,
+    Swiss,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Registration,
+    Running,
+    Finished,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    FirstWon,
+    SecondWon,
+    Draw,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Pairing {
+    /// Plays side 0.
+    pub first: String,
+    /// `None` for a bye, which counts as a win for `first`.
+    pub second: Option<String>,
+    pub lobby: Option<String>,
+    pub outcome: Option<Outcome>,
+}
+
+impl Pairing {
+    /// Points `player` scored in this pairing, if they took part and it's decided.
+    fn points(&self, player: &str) -> Option<f64> {
+        let outcome = self.outcome?;
+        let first = if self.first == player {
+            true
+        } else if self.second.as_deref() == Some(player) {
+            false
+        } else {
+            return None;
+        };
+        Some(match (outcome, first) {
+            (Outcome::Draw, _) => 0.5,
+            (Outcome::FirstWon, true) | (Outcome::SecondWon, false) => 1.0,
+            _ => 0.0,
+        })
+    }
+
+    fn opponent(&self, player: &str) -> Option<&str> {
+        match &self.second {
+            Some(second) if self.first == player => Some(second),
+            Some(second) if second == player => Some(&self.first),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Standing {
+    pub player: String,
+    pub score: f64,
+    /// Sum of the opponents' scores.
+    pub buchholz: f64,
+    /// Sum of the opponents' scores, weighted by the points scored against them.
+    pub sonneborn_berger: f64,
+    pub wins: u32,
+    pub played: u32,
+}
+
+#[derive(Serialize)]
+pub struct Tournament {
+    pub name: String,
+    #[serde(skip)]
+    game_type: QGameType,
+    #[serde(rename = "game_type")]
+    game_type_name: &'static str,
+    /// The board size every game is played on.
+    pub size: u8,
+    pub format: Format,
+    pub rounds: usize,
+    pub stage: Stage,
+    pub players: Vec<String>,
+    /// Pairings of every round started so far.
+    pub pairings: Vec<Vec<Pairing>>,
+}
+
+impl Tournament {
+    fn new(
+        name: String,
+        game_type: QGameType,
+        size: u8,
+        format: Format,
+        rounds: Option<usize>,
+    ) -> Self {
+        Tournament {
+            name,
+            game_type,
+            game_type_name: gtstr(&game_type),
+            size,
+            format,
+            rounds: rounds.unwrap_or(0),
+            stage: Stage::Registration,
+            players: vec![],
+            pairings: vec![],
+        }
+    }
+
+    fn register(&mut self, player: String) -> Result<(), &'static str> {
+        let player = player.trim().to_string();
+        if self.stage != Stage::Registration {
+            return Err("registration is closed");
+        }
+        if player.is_empty() {
+            return Err("player name can't be empty");
+        }
+        if self.players.contains(&player) {
+            return Err("player is already registered");
+        }
+        self.players.push(player);
+        Ok(())
+    }
+
+    fn start(
+        &mut self,
+        lobbies: &mut HashMap<String, Lobby>,
+        games: &HashMap<String, GameFn>,
+        events: &Events,
+    ) -> Result<(), &'static str> {
+        if self.stage != Stage::Registration {
+            return Err("tournament has already started");
+        }
+        if self.players.len() < 2 {
+            return Err("at least two players are needed");
+        }
+        let n = self.players.len();
+        self.rounds = match self.format {
+            Format::RoundRobin => n - 1 + n % 2,
+            Format::Swiss if self.rounds == 0 => (n as f64).log2().ceil() as usize,
+            Format::Swiss => self.rounds,
+        };
+        self.stage = Stage::Running;
+        self.next_round(lobbies, games, events);
+        Ok(())
+    }
+
+    /// Records the outcome of one of this tournament's games, if it was one.
+    fn record(&mut self, lobby: &str, outcome: Outcome) -> bool {
+        let pairing = self
+            .pairings
+            .iter_mut()
+            .flatten()
+            .find(|p| p.lobby.as_deref() == Some(lobby) && p.outcome.is_none());
+        match pairing {
+            Some(pairing) => {
+                pairing.outcome = Some(outcome);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Starts the next round, or finishes the tournament, once the current round is decided.
+    fn advance(
+        &mut self,
+        lobbies: &mut HashMap<String, Lobby>,
+        games: &HashMap<String, GameFn>,
+        events: &Events,
+    ) {
+        let decided = self
+            .pairings
+            .last()
+            .map_or(false, |round| round.iter().all(|p| p.outcome.is_some()));
+        if self.stage != Stage::Running || !decided {
+            return;
+        }
+        if self.pairings.len() >= self.rounds {
+            self.stage = Stage::Finished;
+        } else {
+            self.next_round(lobbies, games, events);
+        }
+    }
+
+    fn next_round(
+        &mut self,
+        lobbies: &mut HashMap<String, Lobby>,
+        games: &HashMap<String, GameFn>,
+        events: &Events,
+    ) {
+        let round = self.pairings.len();
+        let pairs = match self.format {
+            Format::RoundRobin => round_robin(&self.players, round),
+            Format::Swiss => self.swiss(),
+        };
+
+        let pairings = pairs
+            .into_iter()
+            .enumerate()
+            .map(|(board, (first, second))| match second {
+                Some(second) => {
+                    let base = format!("{}-r{}-b{}", self.name, round + 1, board + 1);
+                    // Someone may have opened a lobby, or started a game, by that name already.
+                    let mut lobby = base.clone();
+                    let mut n = 1;
+                    while lobbies.contains_key(&lobby) || games.contains_key(&lobby) {
+                        n += 1;
+                        lobby = format!("{}-{}", base, n);
+                    }
+                    let game = Lobby::new(self.game_type, self.size);
+                    announce(
+                        events,
+                        ServerEvent::LobbyCreated(LobbyInfo::new(&lobby, &game)),
+                    );
+                    lobbies.insert(lobby.clone(), game);
+                    Pairing {
+                        first,
+                        second: Some(second),
+                        lobby: Some(lobby),
+                        outcome: None,
+                    }
+                }
+                None => Pairing {
+                    first,
+                    second: None,
+                    lobby: None,
+                    outcome: Some(Outcome::FirstWon),
+                },
+            })
+            .collect();
+        self.pairings.push(pairings);
+
+        // A round made only of byes is already decided.
+        self.advance(lobbies, games, events);
+    }
+
+    fn swiss(&self) -> Vec<(String, Option<String>)> {
+        let mut order: Vec<String> = self.standings().into_iter().map(|s| s.player).collect();
+        let mut pairs = vec![];
+
+        if order.len() % 2 == 1 {
+            // The bye goes to the lowest ranked player who hasn't had one yet.
+            let had_bye = |player: &String| {
+                self.pairings
+                    .iter()
+                    .flatten()
+                    .any(|p| p.second.is_none() && &p.first == player)
+            };
+            let i = order
+                .iter()
+                .rposition(|p| !had_bye(p))
+                .unwrap_or(order.len() - 1);
+            pairs.push((order.remove(i), None));
+        }
+
+        let have_played = |a: &str, b: &str| {
+            self.pairings
+                .iter()
+                .flatten()
+                .any(|p| p.opponent(a) == Some(b))
+        };
+        let matched = pair_up(&order, &have_played).unwrap_or_else(|| {
+            // Everyone left has met already, so allow rematches.
+            order
+                .chunks(2)
+                .map(|pair| (pair[0].clone(), pair[1].clone()))
+                .collect()
+        });
+
+        let firsts = |player: &str| {
+            self.pairings
+                .iter()
+                .flatten()
+                .filter(|p| p.first == player && p.second.is_some())
+                .count()
+        };
+        for (a, b) in matched {
+            if firsts(&a) <= firsts(&b) {
+                pairs.push((a, Some(b)));
+            } else {
+                pairs.push((b, Some(a)));
+            }
+        }
+        pairs
+    }
+
+    /// Players ordered by score, then Buchholz, Sonneborn-Berger and wins.
+    pub fn standings(&self) -> Vec<Standing> {
+        let decided: Vec<&Pairing> = self
+            .pairings
+            .iter()
+            .flatten()
+            .filter(|p| p.outcome.is_some())
+            .collect();
+        let score = |player: &str| decided.iter().filter_map(|p| p.points(player)).sum::<f64>();
+
+        let mut standings: Vec<Standing> = self
+            .players
+            .iter()
+            .map(|player| {
+                let mut standing = Standing {
+                    player: player.clone(),
+                    score: score(player),
+                    buchholz: 0.0,
+                    sonneborn_berger: 0.0,
+                    wins: 0,
+                    played: 0,
+                };
+                for pairing in decided.iter() {
+                    let points = match pairing.points(player) {
+                        Some(points) => points,
+                        None => continue,
+                    };
+                    if points > 0.5 {
+                        standing.wins += 1;
+                    }
+                    if let Some(opponent) = pairing.opponent(player) {
+                        let opponent_score = score(opponent);
+                        standing.played += 1;
+                        standing.buchholz += opponent_score;
+                        standing.sonneborn_berger += points * opponent_score;
+                    }
+                }
+                standing
+            })
+            .collect();
+
+        standings.sort_by(|a, b| {
+            let key = |s: &Standing| (s.score, s.buchholz, s.sonneborn_berger, s.wins as f64);
+            key(b)
+                .partial_cmp(&key(a))
+                .unwrap()
+                .then_with(|| a.player.cmp(&b.player))
+        });
+        standings
+    }
+}
+
+/// Pairs for `round` of a round robin, using the circle method.
+fn round_robin(players: &[String], round: usize) -> Vec<(String, Option<String>)> {
+    let mut seats: Vec<Option<&String>> = players.iter().map(Some).collect();
+    if seats.len() % 2 == 1 {
+        seats.push(None);
+    }
+    let n = seats.len();
+
+    // The first seat stays put while the others rotate one step per round.
+    let rotated: Vec<_> = std::iter::once(seats[0])
+        .chain((0..n - 1).map(|i| seats[1 + (i + round) % (n - 1)]))
+        .collect();
+
+    (0..n / 2)
+        .filter_map(|i| {
+            let (a, b) = (rotated[i], rotated[n - 1 - i]);
+            // Swap sides every other round so colours stay balanced.
+            let (a, b) = if round % 2 == 1 { (b, a) } else { (a, b) };
+            match (a, b) {
+                (Some(a), Some(b)) => Some((a.clone(), Some(b.clone()))),
+                (Some(a), None) | (None, Some(a)) => Some((a.clone(), None)),
+                (None, None) => None,
+            }
+        })
+        .collect()
+}
+
+/// Pairs players in order, each with the highest ranked opponent they haven't met.
+fn pair_up(
+    players: &[String],
+    have_played: &dyn Fn(&str, &str) -> bool,
+) -> Option<Vec<(String, String)>> {
+    let (first, rest) = match players.split_first() {
+        Some(split) => split,
+        None => return Some(vec![]),
+    };
+    for (i, opponent) in rest.iter().enumerate() {
+        if have_played(first, opponent) {
+            continue;
+        }
+        let remaining: Vec<String> = rest
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .map(|(_, p)| p.clone())
+            .collect();
+        if let Some(mut pairs) = pair_up(&remaining, have_played) {
+            pairs.insert(0, (first.clone(), opponent.clone()));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+/// Feeds the games that end, from a queue of the server's events, into the
+/// tournaments they belong to.
+pub async fn collect_results(
+    tournaments: Tournaments,
+    hub: Hub,
+    mut events: mpsc::UnboundedReceiver<ServerEvent>,
+) {
+    while let Some(event) = events.recv().await {
+        if let ServerEvent::GameEnded { name, winner, .. } = event {
+            let outcome = match winner {
+                Some(0) => Outcome::FirstWon,
+                Some(_) => Outcome::SecondWon,
+                None => Outcome::Draw,
+            };
+            let mut tournaments = tournaments.write().await;
+            if let Some(t) = tournaments.values_mut().find(|t| t.record(&name, outcome)) {
+                let mut lobbies = hub.lobbies.write().await;
+                t.advance(&mut lobbies, &*hub.games.read().await, &hub.events);
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct NewTournament {
+    name: String,
+    game_type: String,
+    #[serde(default = "default_size")]
+    size: u8,
+    format: Format,
+    #[serde(default)]
+    rounds: String,
+}
+
+#[derive(Deserialize)]
+struct Registration {
+    player: String,
+}
+
+#[derive(Deserialize)]
+struct ResultOverride {
+    lobby: String,
+    outcome: Outcome,
+}
+
+#[derive(Serialize)]
+struct TournamentSummary<'a> {
+    name: &'a str,
+    game_type: &'static str,
+    size: u8,
+    format: Format,
+    stage: Stage,
+    players: usize,
+}
+
+#[derive(Serialize)]
+struct TournamentView<'a> {
+    #[serde(flatten)]
+    tournament: &'a Tournament,
+    standings: Vec<Standing>,
+}
+
+fn respond(result: Result<(), &'static str>, name: &str) -> warp::reply::Response {
+    match result {
+        Ok(()) => warp::redirect(
+            Uri::builder()
+                .path_and_query(&format!("/tournament/{}", name)[..])
+                .build()
+                .unwrap(),
+        )
+        .into_response(),
+        Err(e) => warp::reply::with_status(e, StatusCode::BAD_REQUEST).into_response(),
+    }
+}
+
+pub fn routes(
+    tournaments: Tournaments,
+    hub: Hub,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = Rejection> + Clone {
+    let create = warp::post()
+        .and(path!("tournament" / "new"))
+        .and(warp::body::form())
+        .and(warpify!(tournaments))
+        .and_then(|form: NewTournament, tournaments: Tournaments| async move {
+            let game_type = match &form.game_type[..] {
+                "standard" => QGameType::StandardQuoridor,
+                "free" => QGameType::FreeQuoridor,
+                _ => return Err(warp::reject::custom(UnimplementedGameType)),
+            };
+            if !BOARD_SIZES.contains(&form.size) {
+                return Err(warp::reject::custom(UnsupportedBoardSize));
+            }
+            let mut tournaments = tournaments.write().await;
+            let result = if tournaments.contains_key(&form.name) {
+                Err("a tournament with that name already exists")
+            } else {
+                let rounds = form.rounds.trim().parse().ok();
+                let t =
+                    Tournament::new(form.name.clone(), game_type, form.size, form.format, rounds);
+                tournaments.insert(form.name.clone(), t);
+                Ok(())
+            };
+            Ok(respond(result, &form.name))
+        });
+
+    let register = warp::post()
+        .and(path!("tournament" / String / "register"))
+        .and(warp::body::form())
+        .and(warpify!(tournaments))
+        .and_then(
+            |name: String, form: Registration, tournaments: Tournaments| async move {
+                let result = match tournaments.write().await.get_mut(&name) {
+                    Some(t) => t.register(form.player),
+                    None => return Err(warp::reject::not_found()),
+                };
+                Ok(respond(result, &name))
+            },
+        );
+
+    let start = warp::post()
+        .and(path!("tournament" / String / "start"))
+        .and(warpify!(tournaments))
+        .and(warpify!(hub))
+        .and_then(
+            |name: String, tournaments: Tournaments, hub: Hub| async move {
+                let result = match tournaments.write().await.get_mut(&name) {
+                    Some(t) => {
+                        let mut lobbies = hub.lobbies.write().await;
+                        t.start(&mut lobbies, &*hub.games.read().await, &hub.events)
+                    }
+                    None => return Err(warp::reject::not_found()),
+                };
+                Ok(respond(result, &name))
+            },
+        );
+
+    // For games that were abandoned or decided over the board.
+    let set_result = warp::post()
+        .and(path!("tournament" / String / "result"))
+        .and(warp::body::form())
+        .and(warpify!(tournaments))
+        .and(warpify!(hub))
+        .and_then(
+            |name: String, form: ResultOverride, tournaments: Tournaments, hub: Hub| async move {
+                let mut tournaments = tournaments.write().await;
+                let t = tournaments
+                    .get_mut(&name)
+                    .ok_or_else(warp::reject::not_found)?;
+                let result = if t.record(&form.lobby, form.outcome) {
+                    let mut lobbies = hub.lobbies.write().await;
+                    if lobbies.remove(&form.lobby).is_some() {
+                        announce(
+                            &hub.events,
+                            ServerEvent::LobbyRemoved {
+                                name: form.lobby.clone(),
+                            },
+                        );
+                    }
+                    t.advance(&mut lobbies, &*hub.games.read().await, &hub.events);
+                    Ok(())
+                } else {
+                    Err("no undecided game in that lobby")
+                };
+                Ok::<_, Rejection>(respond(result, &name))
+            },
+        );
+
+    let list = warp::get()
+        .and(path!("tournament" / "list"))
+        .and(warpify!(tournaments))
+        .and_then(|tournaments: Tournaments| async move {
+            let tournaments = tournaments.read().await;
+            let summaries: Vec<_> = tournaments
+                .values()
+                .map(|t| TournamentSummary {
+                    name: &t.name,
+                    game_type: t.game_type_name,
+                    size: t.size,
+                    format: t.format,
+                    stage: t.stage,
+                    players: t.players.len(),
+                })
+                .collect();
+            Ok::<_, Infallible>(warp::reply::json(&summaries).into_response())
+        });
+
+    let standings = warp::get()
+        .and(path!("tournament" / String / "standings"))
+        .and(warpify!(tournaments))
+        .and_then(|name: String, tournaments: Tournaments| async move {
+            let tournaments = tournaments.read().await;
+            let tournament = tournaments.get(&name).ok_or_else(warp::reject::not_found)?;
+            let view = TournamentView {
+                standings: tournament.standings(),
+                tournament,
+            };
+            Ok::<_, Rejection>(warp::reply::json(&view).into_response())
+        });
+
+    let page = warp::get()
+        .and(path!("tournament" / String))
+        .and(warp::fs::file("./static/tournament.html"))
+        .map(|_, f: warp::fs::File| f.into_response());
+
+    create
+        .or(register)
+        .unify()
+        .or(start)
+        .unify()
+        .or(set_result)
+        .unify()
+        .or(list)
+        .unify()
+        .or(standings)
+        .unify()
+        .or(page)
+        .unify()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn tournament(players: usize, format: Format, rounds: Option<usize>) -> Tournament {
+        let mut t = Tournament::new(
+            "cup".into(),
+            QGameType::StandardQuoridor,
+            BOARD_SIZE,
+            format,
+            rounds,
+        );
+        for i in 1..=players {
+            t.register(format!("p{}", i)).unwrap();
+        }
+        t
+    }
+
+    /// Decides every open game of the current round as a win for side 0.
+    fn play_round(t: &mut Tournament, lobbies: &mut HashMap<String, Lobby>, events: &Events) {
+        let games = HashMap::new();
+        let open: Vec<String> = t
+            .pairings
+            .last()
+            .unwrap()
+            .iter()
+            .filter(|p| p.outcome.is_none())
+            .filter_map(|p| p.lobby.clone())
+            .collect();
+        for lobby in open {
+            assert!(t.record(&lobby, Outcome::FirstWon));
+        }
+        t.advance(lobbies, &games, events);
+    }
+
+    fn pair(a: &str, b: &str) -> (String, String) {
+        if a < b {
+            (a.into(), b.into())
+        } else {
+            (b.into(), a.into())
+        }
+    }
+
+    #[test]
+    fn round_robin_pairs_everyone_once() {
+        for n in 2..=8 {
+            let players: Vec<String> = (1..=n).map(|i| format!("p{}", i)).collect();
+            let rounds = n - 1 + n % 2;
+            let mut met = HashSet::new();
+            let mut byes = vec![];
+            for round in 0..rounds {
+                let mut seen = HashSet::new();
+                for (first, second) in round_robin(&players, round) {
+                    assert!(seen.insert(first.clone()), "{} twice in a round", first);
+                    match second {
+                        Some(second) => {
+                            assert!(seen.insert(second.clone()));
+                            assert!(met.insert(pair(&first, &second)), "repeat pairing");
+                        }
+                        None => byes.push(first),
+                    }
+                }
+                assert_eq!(seen.len(), n);
+            }
+            assert_eq!(met.len(), n * (n - 1) / 2);
+            // With an odd field everyone sits out exactly once.
+            let distinct: HashSet<_> = byes.iter().collect();
+            assert_eq!(byes.len(), n % 2 * n);
+            assert_eq!(distinct.len(), byes.len());
+        }
+    }
+
+    #[test]
+    fn swiss_avoids_repeat_pairings() {
+        let mut t = tournament(6, Format::Swiss, Some(3));
+        let mut lobbies = HashMap::new();
+        let events = events::channel();
+        t.start(&mut lobbies, &HashMap::new(), &events).unwrap();
+        while t.stage == Stage::Running {
+            play_round(&mut t, &mut lobbies, &events);
+        }
+        assert_eq!(t.pairings.len(), 3);
+        let mut met = HashSet::new();
+        for p in t.pairings.iter().flatten() {
+            let second = p.second.as_ref().unwrap();
+            assert!(met.insert(pair(&p.first, second)), "repeat pairing");
+        }
+    }
+
+    #[test]
+    fn swiss_byes_go_to_different_players() {
+        let mut t = tournament(5, Format::Swiss, Some(3));
+        let mut lobbies = HashMap::new();
+        let events = events::channel();
+        t.start(&mut lobbies, &HashMap::new(), &events).unwrap();
+        while t.stage == Stage::Running {
+            play_round(&mut t, &mut lobbies, &events);
+        }
+        let byes: Vec<&String> = t
+            .pairings
+            .iter()
+            .flatten()
+            .filter(|p| p.second.is_none())
+            .map(|p| &p.first)
+            .collect();
+        assert_eq!(byes.len(), 3);
+        assert_eq!(byes.iter().collect::<HashSet<_>>().len(), 3);
+        // Byes are decided as soon as they're paired.
+        assert!(t
+            .pairings
+            .iter()
+            .flatten()
+            .filter(|p| p.second.is_none())
+            .all(|p| p.outcome == Some(Outcome::FirstWon)));
+    }
+
+    #[test]
+    fn pair_up_gives_up_when_everyone_has_met() {
+        let players: Vec<String> = vec!["a".into(), "b".into()];
+        assert!(pair_up(&players, &|_, _| true).is_none());
+        let pairs = pair_up(&players, &|_, _| false).unwrap();
+        assert_eq!(pairs, vec![("a".to_string(), "b".to_string())]);
+    }
+
+    #[test]
+    fn standings_break_ties() {
+        let mut t = tournament(3, Format::RoundRobin, None);
+        let game = |first: &str, second: Option<&str>, outcome| Pairing {
+            first: first.into(),
+            second: second.map(String::from),
+            lobby: None,
+            outcome: Some(outcome),
+        };
+        t.pairings = vec![
+            vec![
+                game("p1", Some("p2"), Outcome::FirstWon),
+                game("p3", None, Outcome::FirstWon),
+            ],
+            vec![
+                game("p3", Some("p1"), Outcome::Draw),
+                game("p2", None, Outcome::FirstWon),
+            ],
+        ];
+
+        let standings = t.standings();
+        let order: Vec<&str> = standings.iter().map(|s| &s.player[..]).collect();
+        // p1 and p3 are level on points; p1 met the stronger field.
+        assert_eq!(order, vec!["p1", "p3", "p2"]);
+
+        let (p1, p3, p2) = (&standings[0], &standings[1], &standings[2]);
+        assert_eq!((p1.score, p3.score, p2.score), (1.5, 1.5, 1.0));
+        // Byes bring points but no opponent.
+        assert_eq!((p1.buchholz, p3.buchholz, p2.buchholz), (2.5, 1.5, 1.5));
+        assert_eq!(
+            (
+                p1.sonneborn_berger,
+                p3.sonneborn_berger,
+                p2.sonneborn_berger
+            ),
+            (1.75, 0.75, 0.0)
+        );
+        assert_eq!((p1.wins, p3.wins, p2.wins), (1, 1, 1));
+        assert_eq!((p1.played, p3.played, p2.played), (2, 1, 1));
+    }
+
+    #[test]
+    fn rounds_leave_existing_lobbies_and_games_alone() {
+        let mut t = tournament(2, Format::RoundRobin, None);
+        let mut lobbies = HashMap::new();
+        lobbies.insert(
+            "cup-r1-b1".to_string(),
+            Lobby::new(QGameType::StandardQuoridor, BOARD_SIZE),
+        );
+        let mut games: HashMap<String, GameFn> = HashMap::new();
+        games.insert(
+            "cup-r1-b1-2".to_string(),
+            Box::new(|| -> Result<MoveResult, Box<dyn Error>> { Err("not played".into()) }),
+        );
+        let events = events::channel();
+        t.start(&mut lobbies, &games, &events).unwrap();
+        assert_eq!(t.pairings[0][0].lobby.as_deref(), Some("cup-r1-b1-3"));
+        assert_eq!(lobbies.len(), 2);
+    }
+}
@@ -0,0 +1,263 @@
+//! Outgoing webhooks. Every `[[webhook]]` in the configuration gets a JSON POST
+//! for each game event it asks for:
+//!
+//! ```json
+//! {"delivery": "5f3a9c1e-12", "sent_at": 1602950400, "event": "move_played",
+//!  "game": "friday", "player": 1, "notation": "e5h"}
+//! ```
+//!
+//! `lobby_created` and `game_started` carry the lobby as `/lobby/list` lists it
+//! under `lobby`, and `game_ended` the `winner`, `null` for a draw. The body is
+//! signed with the target's secret as `X-Quoridor-Signature: sha256=<hex>`, the
+//! HMAC-SHA256 of the body. Failed deliveries are retried with a growing delay,
+//! and each target gets its deliveries in order.
+
+use super::*;
+use config::{WebhookConfig, WebhookEvent};
+use hmac::{Hmac, Mac, NewMac};
+use hyper_tls::HttpsConnector;
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::time::{delay_for, timeout};
+use warp::hyper::{body::Bytes, client::HttpConnector, Body, Client, Request};
+
+type HttpsClient = Client<HttpsConnector<HttpConnector>>;
+
+/// Deliveries waiting for a target beyond this many are dropped, so a target
+/// that is down for long doesn't hold on to every event meanwhile.
+const QUEUE_LENGTH: usize = 256;
+const ATTEMPTS: u32 = 5;
+/// The wait before the first retry, doubled before every further one.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Payload<'a> {
+    LobbyCreated {
+        lobby: &'a LobbyInfo,
+    },
+    GameStarted {
+        lobby: &'a LobbyInfo,
+    },
+    MovePlayed {
+        game: &'a str,
+        player: PlayerID,
+        notation: &'a str,
+    },
+    GameEnded {
+        game: &'a str,
+        winner: Option<PlayerID>,
+    },
+}
+
+impl<'a> Payload<'a> {
+    /// The payload for an event, `None` for events no webhook is sent for.
+    fn of(event: &'a ServerEvent) -> Option<Self> {
+        Some(match event {
+            ServerEvent::LobbyCreated(lobby) => Payload::LobbyCreated { lobby },
+            ServerEvent::GameStarted(lobby) => Payload::GameStarted { lobby },
+            ServerEvent::MovePlayed {
+                name,
+                player,
+                notation,
+            } => Payload::MovePlayed {
+                game: name,
+                player: *player,
+                notation,
+            },
+            ServerEvent::GameEnded { name, winner, .. } => Payload::GameEnded {
+                game: name,
+                winner: *winner,
+            },
+            ServerEvent::LobbyUpdated(_) | ServerEvent::LobbyRemoved { .. } => return None,
+        })
+    }
+
+    fn kind(&self) -> WebhookEvent {
+        match self {
+            Payload::LobbyCreated { .. } => WebhookEvent::LobbyCreated,
+            Payload::GameStarted { .. } => WebhookEvent::GameStarted,
+            Payload::MovePlayed { .. } => WebhookEvent::MovePlayed,
+            Payload::GameEnded { .. } => WebhookEvent::GameEnded,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    delivery: &'a str,
+    /// Seconds since the Unix epoch.
+    sent_at: u64,
+    #[serde(flatten)]
+    payload: Payload<'a>,
+}
+
+/// A body on its way to one target, the same for every target it goes to.
+#[derive(Clone)]
+struct Delivery {
+    id: String,
+    event: &'static str,
+    body: Bytes,
+}
+
+/// The hex HMAC-SHA256 of `body` under `secret`.
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC takes any key");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Sends the webhooks for `events` to `targets` until the server stops.
+pub async fn dispatch(
+    targets: Vec<WebhookConfig>,
+    mut events: mpsc::UnboundedReceiver<ServerEvent>,
+) {
+    let client: HttpsClient = Client::builder().build(HttpsConnector::new());
+    let mut queues: Vec<_> = targets
+        .into_iter()
+        .map(|target| {
+            let (sender, receiver) = mpsc::channel(QUEUE_LENGTH);
+            let wanted = target.events.clone();
+            tokio::spawn(deliver(client.clone(), target, receiver));
+            (wanted, sender)
+        })
+        .collect();
+
+    // Delivery ids only need to tell deliveries apart, across restarts too.
+    let boot = unix_time();
+    let mut sequence = 0u64;
+    while let Some(event) = events.recv().await {
+        let payload = match Payload::of(&event) {
+            Some(payload) => payload,
+            None => continue,
+        };
+        sequence += 1;
+        let id = format!("{:x}-{}", boot, sequence);
+        let kind = payload.kind();
+        let envelope = Envelope {
+            delivery: &id,
+            sent_at: unix_time(),
+            payload,
+        };
+        let delivery = Delivery {
+            body: serde_json::to_vec(&envelope).unwrap().into(),
+            event: kind.name(),
+            id,
+        };
+        for (wanted, queue) in queues.iter_mut() {
+            if !wanted.is_empty() && !wanted.contains(&kind) {
+                continue;
+            }
+            if queue.try_send(delivery.clone()).is_err() {
+                warn!(delivery = %delivery.id, "webhook queue full, delivery dropped");
+            }
+        }
+    }
+}
+
+/// Posts the deliveries for one target in turn, retrying each a few times.
+async fn deliver(client: HttpsClient, target: WebhookConfig, mut queue: mpsc::Receiver<Delivery>) {
+    while let Some(delivery) = queue.recv().await {
+        let signature = format!("sha256={}", sign(&target.secret, &delivery.body));
+        let mut delay = FIRST_RETRY_DELAY;
+        for attempt in 1..=ATTEMPTS {
+            match post(&client, &target.url, &delivery, &signature).await {
+                Ok(()) => break,
+                Err(e) if attempt == ATTEMPTS => warn!(
+                    url = %target.url,
+                    delivery = %delivery.id,
+                    error = %e,
+                    "webhook delivery given up"
+                ),
+                Err(e) => {
+                    warn!(
+                        url = %target.url,
+                        delivery = %delivery.id,
+                        error = %e,
+                        attempt,
+                        retry_in = ?delay,
+                        "webhook delivery failed"
+                    );
+                    delay_for(delay).await;
+                    delay *= 2;
+                }
+            }
+        }
+    }
+}
+
+async fn post(
+    client: &HttpsClient,
+    url: &str,
+    delivery: &Delivery,
+    signature: &str,
+) -> Result<(), String> {
+    let request = Request::post(url)
+        .header("Content-Type", "application/json")
+        .header("X-Quoridor-Event", delivery.event)
+        .header("X-Quoridor-Delivery", &delivery.id[..])
+        .header("X-Quoridor-Signature", signature)
+        .body(Body::from(delivery.body.clone()))
+        .map_err(|e| e.to_string())?;
+    let response = timeout(REQUEST_TIMEOUT, client.request(request))
+        .await
+        .map_err(|_| String::from("timed out"))?
+        .map_err(|e| e.to_string())?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("status {}", response.status()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            sign("", b""),
+            "b613679a0814d9ec772f95d778c35fc5ff1697c493715653c6c712144292c5ad"
+        );
+    }
+
+    #[test]
+    fn bodies_are_named_as_configured() {
+        let events = vec![
+            ServerEvent::MovePlayed {
+                name: "friday".into(),
+                player: 1,
+                notation: "e5h".into(),
+            },
+            ServerEvent::GameEnded {
+                name: "friday".into(),
+                winner: None,
+                termination: Termination::Goal,
+            },
+        ];
+        for event in &events {
+            let payload = Payload::of(event).unwrap();
+            let kind = payload.kind();
+            let envelope = Envelope {
+                delivery: "5f3a9c1e-12",
+                sent_at: 1602950400,
+                payload,
+            };
+            let body = serde_json::to_value(&envelope).unwrap();
+            assert_eq!(body["event"], kind.name());
+            assert_eq!(body["game"], "friday");
+            assert_eq!(body["delivery"], "5f3a9c1e-12");
+        }
+        let removed = ServerEvent::LobbyRemoved {
+            name: "friday".into(),
+        };
+        assert!(Payload::of(&removed).is_none());
+    }
+}
@@ -0,0 +1,16 @@
+// Runs the client's AI off the page's thread; see client/src/ai.rs.
+import init, { best_move } from "/static/pkg/client.js";
+
+const ready = init();
+
+// Always answers, with null when there's no move, so the page stops waiting.
+onmessage = async (e) => {
+    let reply = null;
+    try {
+        await ready;
+        reply = best_move(e.data) ?? null;
+    } catch (err) {
+        console.error(err);
+    }
+    postMessage(reply);
+};
@@ -0,0 +1,195 @@
+use bimap::BiMap;
+use crossbeam_channel::{Receiver, Sender};
+use quoridor_core::{rulebooks::*, *};
+use serde::Deserialize;
+use std::{
+    error::Error,
+    io::{self, BufRead, Write},
+    thread,
+    time::Duration,
+};
+use tbmp_core::*;
+
+mod render;
+mod ws;
+
+use common::{
+    board::Board,
+    notation::{format_move, parse_move},
+};
+use render::{render_board, render_status};
+
+generate_rulebook! {
+    [NO CONNECT]
+    FreeQuoridor,
+    StandardQuoridor,
+}
+
+const DEFAULT_SERVER: &str = "localhost:3030";
+const HELP: &str = "Moves use algebraic notation: e2 moves your pawn to e2, \
+e3h places a horizontal wall right above e3 and e3v a vertical one right of it. \
+Type 'board' to redraw, 'help' for this message and 'quit' to leave.";
+
+#[derive(Deserialize)]
+struct LobbyEntry {
+    game_type: String,
+    name: String,
+    size: u8,
+    status: String,
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let server = args.next().unwrap_or_else(|| DEFAULT_SERVER.into());
+    let lobby = args.next();
+
+    if let Err(e) = run(&server, lobby) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(server: &str, lobby: Option<String>) -> Result<(), Box<dyn Error>> {
+    let entry = match lobby {
+        // Private lobbies aren't listed, but can be looked up by name.
+        Some(name) => {
+            let url = format!("http://{}/lobby/info/{}", server, name);
+            let response = ureq::get(&url).call();
+            let entry: LobbyEntry = match response.status() {
+                200 => response.into_json_deserialize()?,
+                _ => return Err(format!("no lobby named '{}'", name).into()),
+            };
+            if entry.status != "waiting" {
+                return Err(format!("lobby '{}' has no free seats", name).into());
+            }
+            entry
+        }
+        None => {
+            let url = format!("http://{}/lobby/list?status=waiting&limit=200", server);
+            pick_lobby(ureq::get(&url).call().into_json_deserialize()?)?
+        }
+    };
+
+    let url = format!("ws://{}/join/{}", server, entry.name);
+    let agent = match &entry.game_type[..] {
+        "free" => QAgent::FreeQuoridor(ws::connect::<QGame<FreeQuoridor>>(&url)?),
+        "standard" => QAgent::StandardQuoridor(ws::connect::<QGame<StandardQuoridor>>(&url)?),
+        other => return Err(format!("unsupported game type '{}'", other).into()),
+    };
+    println!("Joined '{}', waiting for an opponent...", entry.name);
+
+    play(agent)
+}
+
+fn pick_lobby(lobbies: Vec<LobbyEntry>) -> Result<LobbyEntry, Box<dyn Error>> {
+    if lobbies.is_empty() {
+        return Err("there are no open lobbies".into());
+    }
+    for (i, lobby) in lobbies.iter().enumerate() {
+        println!(
+            "{:>3}) {} ({}, {}x{})",
+            i + 1,
+            lobby.name,
+            lobby.game_type,
+            lobby.size,
+            lobby.size
+        );
+    }
+
+    let stdin = io::stdin();
+    loop {
+        print!("Lobby to join: ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Err("no lobby selected".into());
+        }
+        match line.trim().parse::<usize>() {
+            Ok(i) if i >= 1 && i <= lobbies.len() => {
+                return Ok(lobbies.into_iter().nth(i - 1).unwrap())
+            }
+            _ => println!("Enter a number between 1 and {}", lobbies.len()),
+        }
+    }
+}
+
+fn spawn_stdin() -> Receiver<String> {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let sent = line.map(|line| tx.send(line).is_ok()).unwrap_or(false);
+            if !sent {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn play(agent: QAgent) -> Result<(), Box<dyn Error>> {
+    let input = spawn_stdin();
+    let mut state: Option<(Quoridor, PlayerID)> = None;
+
+    loop {
+        while let Ok(event) = agent.recv_event() {
+            match event {
+                QGameEvent::GameStart(game, side) => {
+                    println!("{}\n", HELP);
+                    print_game(&game, side);
+                    state = Some((game, side));
+                }
+                QGameEvent::MoveHappened(qmv) => {
+                    if let Some((game, side)) = &mut state {
+                        println!("Player {} played {}", game.turn_of(), format_move(&qmv));
+                        game.apply_move(&qmv);
+                        print_game(game, *side);
+                    }
+                }
+                QGameEvent::GameEnd(Some(id)) => {
+                    println!("Player {} won!", id);
+                    return Ok(());
+                }
+                QGameEvent::GameEnd(None) => {
+                    println!("Draw!");
+                    return Ok(());
+                }
+                QGameEvent::OpponentQuit => {
+                    println!("Opponent quit!");
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+
+        while let Ok(line) = input.try_recv() {
+            match (line.trim(), &state) {
+                ("quit", _) => return Ok(()),
+                ("help", _) => println!("{}", HELP),
+                ("board", Some((game, side))) => print_game(game, *side),
+                ("", _) => {}
+                (_, None) => println!("The game hasn't started yet"),
+                (text, Some((game, side))) => {
+                    if game.turn_of() != *side {
+                        println!("It's not your turn");
+                        continue;
+                    }
+                    match parse_move(text, game) {
+                        Ok(qmv) if Board::from_game(game).is_legal(&qmv) => {
+                            agent.send_move(RulebookMove::wrap(game, &qmv)).unwrap()
+                        }
+                        Ok(_) => println!("{} isn't a legal move", text),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn print_game(game: &Quoridor, side: PlayerID) {
+    println!("{}", render_board(game, side));
+    println!("{}", render_status(game, side));
+}
@@ -0,0 +1,101 @@
+use common::{board::GameSize, pawn_owner};
+use quoridor_core::*;
+use tbmp_core::*;
+
+const CELL_WIDTH: usize = 4;
+const CELL_HEIGHT: usize = 2;
+
+/// Draws the board as text, seen from `side`'s end of the table.
+pub fn render_board(game: &Quoridor, side: PlayerID) -> String {
+    let size = game.size() as usize;
+    let width = size * CELL_WIDTH + 1;
+    let height = size * CELL_HEIGHT + 1;
+    let mut grid = vec![vec![' '; width]; height];
+
+    // Grid lines are addressed from the bottom left corner, like positions.
+    let mut set = |x: usize, y: usize, c: char| grid[height - 1 - y][x] = c;
+
+    for line in 0..=size {
+        for x in 0..width {
+            set(x, line * CELL_HEIGHT, if x % CELL_WIDTH == 0 { '+' } else { '-' });
+        }
+        for y in 0..height {
+            if y % CELL_HEIGHT != 0 {
+                set(line * CELL_WIDTH, y, '|');
+            }
+        }
+    }
+
+    for wall in game.walls().iter() {
+        let (x, y) = (wall.position.x as usize, wall.position.y as usize);
+        match wall.orientation {
+            Orientation::Horizontal => {
+                for i in (x - 1) * CELL_WIDTH + 1..(x + 1) * CELL_WIDTH {
+                    set(i, y * CELL_HEIGHT, '=');
+                }
+            }
+            Orientation::Vertical => {
+                for i in (y - 1) * CELL_HEIGHT + 1..(y + 1) * CELL_HEIGHT {
+                    set(x * CELL_WIDTH, i, '#');
+                }
+            }
+        }
+    }
+
+    for (&id, &pos) in game.pawns().iter() {
+        let owner = pawn_owner(game, id);
+        let x = pos.x as usize * CELL_WIDTH + CELL_WIDTH / 2;
+        let y = pos.y as usize * CELL_HEIGHT + CELL_HEIGHT / 2;
+        set(x, y, (b'0' + owner) as char);
+    }
+
+    let mut rows: Vec<String> = grid
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let line: String = row.into_iter().collect();
+            let y = height - 1 - i;
+            if y % CELL_HEIGHT == 0 {
+                format!("    {}", line)
+            } else {
+                let label = y / CELL_HEIGHT + 1;
+                format!("{:>3} {} {}", label, line, label)
+            }
+        })
+        .collect();
+
+    // Same orientation as the browser client, which only flips vertically.
+    if side != 0 {
+        rows.reverse();
+    }
+
+    let header = (0..size).fold(String::from("      "), |acc, x| {
+        acc + &format!("{:<width$}", (b'a' + x as u8) as char, width = CELL_WIDTH)
+    });
+
+    let mut out = header.clone();
+    out.push('\n');
+    for row in rows {
+        out.push_str(row.trim_end());
+        out.push('\n');
+    }
+    out.push_str(&header);
+    out.push('\n');
+    out
+}
+
+/// One line summary of walls left and whose turn it is.
+pub fn render_status(game: &Quoridor, side: PlayerID) -> String {
+    let walls = game
+        .wall_counts()
+        .enumerate()
+        .map(|(id, count)| format!("player {}: {} walls", id, count))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let turn = if game.turn_of() == side {
+        String::from("your move")
+    } else {
+        format!("waiting for player {}", game.turn_of())
+    };
+    format!("You are player {} | {} | {}", side, walls, turn)
+}
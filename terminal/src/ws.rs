@@ -0,0 +1,46 @@
+use std::{error::Error, io, net::TcpStream, thread, time::Duration};
+use tbmp_core::*;
+use tungstenite::Message;
+use url::Url;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Joins a lobby over a websocket, speaking the same bincode protocol as the browser client.
+pub fn connect<G: Game>(url: &str) -> Result<AgentCore<G>, Box<dyn Error>> {
+    let url = Url::parse(url)?;
+    let stream = TcpStream::connect(&*url.socket_addrs(|| Some(80))?)?;
+    let (mut socket, _) = tungstenite::client(url, stream)?;
+    socket.get_mut().set_read_timeout(Some(POLL_INTERVAL))?;
+
+    let (etx, erx) = crossbeam_channel::unbounded();
+    let (mtx, mrx) = crossbeam_channel::unbounded::<G::Move>();
+
+    thread::spawn(move || loop {
+        while let Ok(qmove) = mrx.try_recv() {
+            let buf = bincode::serialize(&qmove).unwrap();
+            if socket.write_message(Message::Binary(buf)).is_err() {
+                return;
+            }
+        }
+
+        match socket.read_message() {
+            Ok(Message::Binary(buf)) => {
+                if let Ok(event) = bincode::deserialize::<GameEvent<G>>(&buf) {
+                    if etx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(Message::Close(_)) => return,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {}
+            Err(_) => return,
+        }
+    });
+
+    Ok(AgentCore {
+        event_channel: erx,
+        move_channel: mtx,
+    })
+}